@@ -0,0 +1,415 @@
+use crate::{
+    linalg::{transform::Transform, Quaternion, Vector3},
+    node::NodeId,
+    scene::Scene,
+    utils::EPS,
+};
+use image::RgbImage;
+use std::{
+    error::Error,
+    ops::{Add, Mul},
+    path::Path,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+pub trait Animatable: Copy + Add<Output = Self> + Mul<f32, Output = Self> {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self * (1. - t) + other * t
+    }
+    //  Projects a spline value back onto the valid set, or returns `nearest`
+    //  where it cannot.
+    fn renormalize(self, _nearest: Self) -> Self {
+        self
+    }
+    //  -1 where the value and its negation are the same pose and the
+    //  negation is closer to `other`, else 1.
+    fn sign_towards(self, _other: Self) -> f32 {
+        1.
+    }
+}
+
+impl Animatable for f32 {}
+
+impl Animatable for Vector3 {}
+
+impl Animatable for Quaternion {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self.slerp(other, t)
+    }
+    fn renormalize(self, nearest: Self) -> Self {
+        if self.norm() > EPS {
+            self.normalize()
+        } else {
+            nearest
+        }
+    }
+    fn sign_towards(self, other: Self) -> f32 {
+        if self.dot(other) < 0. {
+            -1.
+        } else {
+            1.
+        }
+    }
+}
+
+//  Tangents are (in, out) derivatives per second. Cubic-spline keys without
+//  them fall back to Catmull-Rom tangents.
+#[derive(Clone, Debug)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+    pub tangents: Option<(T, T)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    pub interp: Interpolation,
+    keys: Vec<Keyframe<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(interp: Interpolation) -> Self {
+        Self {
+            interp,
+            keys: vec![],
+        }
+    }
+
+    pub fn key(mut self, time: f32, value: T) -> Self {
+        self.insert(Keyframe {
+            time,
+            value,
+            tangents: None,
+        });
+        self
+    }
+
+    pub fn key_tangents(mut self, time: f32, value: T, in_tan: T, out_tan: T) -> Self {
+        self.insert(Keyframe {
+            time,
+            value,
+            tangents: Some((in_tan, out_tan)),
+        });
+        self
+    }
+
+    pub fn insert(&mut self, key: Keyframe<T>) {
+        let i = self.keys.partition_point(|k| k.time <= key.time);
+        self.keys.insert(i, key);
+    }
+
+    pub fn keys(&self) -> &[Keyframe<T>] {
+        &self.keys
+    }
+
+    pub fn end_time(&self) -> f32 {
+        self.keys.last().map_or(0., |k| k.time)
+    }
+
+    //  Clamps to the first and last keys outside of the keyed range.
+    pub fn sample(&self, t: f32) -> Option<T> {
+        let (first, last) = (self.keys.first()?, self.keys.last()?);
+        if t <= first.time {
+            return Some(first.value);
+        }
+        if t >= last.time {
+            return Some(last.value);
+        }
+        let i = self.keys.partition_point(|k| k.time <= t) - 1;
+        let (k0, k1) = (&self.keys[i], &self.keys[i + 1]);
+        let dt = k1.time - k0.time;
+        let s = (t - k0.time) / dt;
+        Some(match self.interp {
+            Interpolation::Step => k0.value,
+            Interpolation::Linear => k0.value.lerp(k1.value, s),
+            Interpolation::CubicSpline => {
+                //  Bring the second key and its tangent into the first key's
+                //  hemisphere, so that q and -q do not cancel out.
+                let sign = k1.value.sign_towards(k0.value);
+                let (v1, m0, m1) = (
+                    k1.value * sign,
+                    self.tangent(i).1,
+                    self.tangent(i + 1).0 * sign,
+                );
+                let (s2, s3) = (s * s, s * s * s);
+                let nearest = if s < 0.5 { k0.value } else { v1 };
+                (k0.value * (2. * s3 - 3. * s2 + 1.)
+                    + m0 * ((s3 - 2. * s2 + s) * dt)
+                    + v1 * (-2. * s3 + 3. * s2)
+                    + m1 * ((s3 - s2) * dt))
+                    .renormalize(nearest)
+            }
+        })
+    }
+
+    //  In the hemisphere of key `i`.
+    fn tangent(&self, i: usize) -> (T, T) {
+        if let Some(tan) = self.keys[i].tangents {
+            return tan;
+        }
+        let (a, b) = (
+            &self.keys[i.saturating_sub(1)],
+            &self.keys[(i + 1).min(self.keys.len() - 1)],
+        );
+        let v = self.keys[i].value;
+        let (va, vb) = (
+            a.value * a.value.sign_towards(v),
+            b.value * b.value.sign_towards(v),
+        );
+        let m = if b.time > a.time {
+            (vb + va * -1.) * (1. / (b.time - a.time))
+        } else {
+            a.value * 0.
+        };
+        (m, m)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Channel {
+    Translation(NodeId, Track<Vector3>),
+    Rotation(NodeId, Track<Quaternion>),
+    Scale(NodeId, Track<Vector3>),
+    CameraPos(Track<Vector3>),
+    CameraTarget(Track<Vector3>),
+    CameraFov(Track<f32>),
+    LightIntensity(usize, Track<Vector3>),
+    LightPos(usize, Track<Vector3>),
+    LightDir(usize, Track<Vector3>),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Animation {
+    channels: Vec<Channel>,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn channel(mut self, channel: Channel) -> Self {
+        self.channels.push(channel);
+        self
+    }
+
+    pub fn add_channel(&mut self, channel: Channel) {
+        self.channels.push(channel);
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .map(|c| match c {
+                Channel::Translation(_, tr)
+                | Channel::Scale(_, tr)
+                | Channel::CameraPos(tr)
+                | Channel::CameraTarget(tr)
+                | Channel::LightIntensity(_, tr)
+                | Channel::LightPos(_, tr)
+                | Channel::LightDir(_, tr) => tr.end_time(),
+                Channel::Rotation(_, tr) => tr.end_time(),
+                Channel::CameraFov(tr) => tr.end_time(),
+            })
+            .fold(0., f32::max)
+    }
+
+    //  Poses the scene at time `t`. Node channels replace the matching
+    //  component of the node's current transform, the camera target is
+    //  applied after the camera position.
+    pub fn apply(&self, scene: &mut Scene, t: f32) {
+        let mut target = None;
+        for c in &self.channels {
+            match c {
+                Channel::Translation(id, tr) => {
+                    if let Some(v) = tr.sample(t) {
+                        let node = scene.node_mut(*id);
+                        let (_, r, s) = node.transform.decompose();
+                        node.transform = Transform::from_trs(v, r, s);
+                    }
+                }
+                Channel::Rotation(id, tr) => {
+                    if let Some(q) = tr.sample(t) {
+                        let node = scene.node_mut(*id);
+                        let (p, _, s) = node.transform.decompose();
+                        node.transform = Transform::from_trs(p, q, s);
+                    }
+                }
+                Channel::Scale(id, tr) => {
+                    if let Some(v) = tr.sample(t) {
+                        let node = scene.node_mut(*id);
+                        let (p, r, _) = node.transform.decompose();
+                        node.transform = Transform::from_trs(p, r, v);
+                    }
+                }
+                Channel::CameraPos(tr) => {
+                    if let Some(v) = tr.sample(t) {
+                        scene.camera_mut().pos = v;
+                    }
+                }
+                Channel::CameraTarget(tr) => target = tr.sample(t).or(target),
+                Channel::CameraFov(tr) => {
+                    if let Some(v) = tr.sample(t) {
+                        scene.camera_mut().fov_y = v;
+                    }
+                }
+                Channel::LightIntensity(id, tr) => {
+                    if let Some(v) = tr.sample(t) {
+                        scene.light_mut(*id).set_intensity(v);
+                    }
+                }
+                Channel::LightPos(id, tr) => {
+                    if let Some(v) = tr.sample(t) {
+                        scene.light_mut(*id).set_pos(v);
+                    }
+                }
+                Channel::LightDir(id, tr) => {
+                    if let Some(v) = tr.sample(t) {
+                        scene.light_mut(*id).set_dir(v);
+                    }
+                }
+            }
+        }
+        if let Some(v) = target {
+            let cam = scene.camera_mut();
+            let up = cam.up;
+            cam.aim(v, up);
+        }
+    }
+
+    pub fn render_frames(
+        &self,
+        scene: &mut Scene,
+        seq: &Sequence,
+        width: usize,
+        height: usize,
+        msaa: usize,
+    ) -> Vec<RgbImage> {
+        seq.times()
+            .map(|t| self.render_frame(scene, t, width, height, msaa))
+            .collect()
+    }
+
+    //  Renders every frame of `seq` into `dir` as `frame_0001.png`, ...
+    //  and returns the number of frames written.
+    pub fn render_sequence<P>(
+        &self,
+        scene: &mut Scene,
+        seq: &Sequence,
+        width: usize,
+        height: usize,
+        msaa: usize,
+        dir: P,
+    ) -> Result<usize, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let mut n = 0;
+        for t in seq.times() {
+            let img = self.render_frame(scene, t, width, height, msaa);
+            n += 1;
+            img.save(dir.as_ref().join(format!("frame_{:04}.png", n)))?;
+        }
+        Ok(n)
+    }
+
+    fn render_frame(
+        &self,
+        scene: &mut Scene,
+        t: f32,
+        width: usize,
+        height: usize,
+        msaa: usize,
+    ) -> RgbImage {
+        self.apply(scene, t);
+        let buf = scene.rasterize(width, height, msaa);
+        RgbImage::from_raw(width as u32, height as u32, buf).unwrap()
+    }
+}
+
+//  Time range sampled at a fixed frame rate, both ends inclusive.
+#[derive(Clone, Copy, Debug)]
+pub struct Sequence {
+    pub start: f32,
+    pub end: f32,
+    pub fps: f32,
+}
+
+impl Sequence {
+    pub fn new(start: f32, end: f32, fps: f32) -> Self {
+        assert!(fps > 0. && end >= start);
+        Self { start, end, fps }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        ((self.end - self.start) * self.fps + 1e-3).floor() as usize + 1
+    }
+
+    pub fn times(&self) -> impl Iterator<Item = f32> {
+        let Self { start, fps, .. } = *self;
+        (0..self.frame_count()).map(move |i| start + i as f32 / fps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(interp: Interpolation) -> Track<f32> {
+        Track::new(interp).key(1., 0.).key(2., 1.).key(3., 3.)
+    }
+
+    #[test]
+    fn clamps_outside_keys() {
+        let tr = track(Interpolation::Linear);
+        assert_eq!(tr.sample(0.), Some(0.));
+        assert_eq!(tr.sample(5.), Some(3.));
+        assert_eq!(Track::<f32>::new(Interpolation::Linear).sample(1.), None);
+    }
+
+    #[test]
+    fn step_and_linear() {
+        assert_eq!(track(Interpolation::Step).sample(1.9), Some(0.));
+        assert_eq!(track(Interpolation::Step).sample(2.5), Some(1.));
+        let tr = track(Interpolation::Linear);
+        assert!((tr.sample(1.25).unwrap() - 0.25).abs() < 1e-6);
+        assert!((tr.sample(2.5).unwrap() - 2.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cubic_passes_keys_and_follows_tangents() {
+        let tr = track(Interpolation::CubicSpline);
+        for (t, v) in [(1., 0.), (2., 1.), (3., 3.)] {
+            assert!((tr.sample(t).unwrap() - v).abs() < 1e-6);
+        }
+        //  A straight line with matching tangents stays straight.
+        let tr = Track::new(Interpolation::CubicSpline)
+            .key_tangents(0., 0., 2., 2.)
+            .key_tangents(1., 2., 2., 2.);
+        assert!((tr.sample(0.3).unwrap() - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cubic_quaternion_across_hemispheres() {
+        let q = Quaternion::new(1., 0., 0., 0.);
+        let tr = Track::new(Interpolation::CubicSpline)
+            .key(0., q)
+            .key(1., -q)
+            .key(2., q);
+        for t in [0.25, 0.5, 0.75, 1.5] {
+            let r = tr.sample(t).unwrap();
+            assert!((r.norm() - 1.).abs() < 1e-5);
+            assert!(r.dot(q).abs() > 1. - 1e-5);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{environment::EquirectMap, linalg::Vector3, texture::Texture};
+use image::ImageResult;
+use std::{path::Path, rc::Rc};
+
+//  What is seen where no geometry covers a sample.
+#[derive(Clone, Debug)]
+pub enum Background {
+    Color(Vector3),
+    //  Vertical gradient across the image.
+    Gradient { top: Vector3, bottom: Vector3 },
+    Cubemap(Rc<Cubemap>),
+    Panorama(Rc<EquirectMap>),
+    //  The scene's lighting environment, black if there is none.
+    Environment,
+    //  Black with zero alpha, for RGBA output.
+    Transparent,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color(Vector3::new(0., 0., 0.))
+    }
+}
+
+//  Faces in the order +x, -x, +y, -y, +z, -z, oriented as in the OpenGL
+//  cube map convention that skybox images are usually authored for.
+#[derive(Debug)]
+pub struct Cubemap {
+    faces: [Texture; 6],
+}
+
+impl Cubemap {
+    pub fn new(faces: [Texture; 6]) -> Self {
+        Self { faces }
+    }
+
+    pub fn open<P>(paths: [P; 6]) -> ImageResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let [px, nx, py, ny, pz, nz] = paths;
+        Ok(Self::new([
+            Texture::open(px)?,
+            Texture::open(nx)?,
+            Texture::open(py)?,
+            Texture::open(ny)?,
+            Texture::open(pz)?,
+            Texture::open(nz)?,
+        ]))
+    }
+
+    pub fn at_dir(&self, d: Vector3) -> Vector3 {
+        let [x, y, z] = d.v;
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        //  Face index and the (s, t) coordinates in [-1, 1] on that face.
+        let (face, s, t) = if ax >= ay && ax >= az {
+            if x > 0. {
+                (0, -z / ax, y / ax)
+            } else {
+                (1, z / ax, y / ax)
+            }
+        } else if ay >= az {
+            if y > 0. {
+                (2, x / ay, -z / ay)
+            } else {
+                (3, x / ay, z / ay)
+            }
+        } else if z > 0. {
+            (4, x / az, y / az)
+        } else {
+            (5, -x / az, y / az)
+        };
+        self.faces[face].sample((s + 1.) / 2., (t + 1.) / 2.)
+    }
+}
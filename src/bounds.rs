@@ -0,0 +1,179 @@
+use crate::linalg::{Matrix4, Vector3, Vector4};
+
+//  Axis-aligned bounding box. The empty box has `min` above `max`.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    pub fn empty() -> Self {
+        Self {
+            min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = Vector3>,
+    {
+        let mut ret = Self::empty();
+        for p in points {
+            ret.grow(p);
+        }
+        ret
+    }
+
+    pub fn grow(&mut self, p: Vector3) {
+        for k in 0..3 {
+            self.min.v[k] = self.min.v[k].min(p.v[k]);
+            self.max.v[k] = self.max.v[k].max(p.v[k]);
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut ret = *self;
+        if other.is_empty() {
+            return ret;
+        }
+        ret.grow(other.min);
+        ret.grow(other.max);
+        ret
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|k| self.min.v[k] > self.max.v[k])
+    }
+
+    pub fn center(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vector3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.;
+        }
+        let e = self.extent();
+        2. * (e.v[0] * e.v[1] + e.v[1] * e.v[2] + e.v[2] * e.v[0])
+    }
+
+    //  Box around the transformed box, for affine `mat`.
+    pub fn transform(&self, mat: Matrix4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        let mut ret = Self {
+            min: Vector3::new(mat.v[0][3], mat.v[1][3], mat.v[2][3]),
+            max: Vector3::new(mat.v[0][3], mat.v[1][3], mat.v[2][3]),
+        };
+        for i in 0..3 {
+            for j in 0..3 {
+                let (a, b) = (mat.v[i][j] * self.min.v[j], mat.v[i][j] * self.max.v[j]);
+                ret.min.v[i] += a.min(b);
+                ret.max.v[i] += a.max(b);
+            }
+        }
+        ret
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sphere {
+    pub center: Vector3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    //  Sphere around the points, centered on their bounding box.
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = Vector3> + Clone,
+    {
+        let center = Aabb::from_points(points.clone()).center();
+        let radius = points
+            .into_iter()
+            .map(|p| (p - center).norm())
+            .fold(0., f32::max);
+        Self { center, radius }
+    }
+
+    //  Sphere around the transformed sphere, for affine `mat`.
+    pub fn transform(&self, mat: Matrix4) -> Self {
+        let scale = (0..3)
+            .map(|j| Vector3::new(mat.v[0][j], mat.v[1][j], mat.v[2][j]).norm())
+            .fold(0., f32::max);
+        Self {
+            center: (mat * self.center.homo_point()).vec3_homo(),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+//  The six planes bounding a view volume, as (n, d) with n.p + d >= 0
+//  inside and n of unit length.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub planes: [Vector4; 6],
+}
+
+impl Frustum {
+    //  Planes of the volume -w <= x, y, z <= w that `mat` maps to clip
+    //  space.
+    pub fn from_matrix(mat: Matrix4) -> Self {
+        let row = |i: usize| Vector4::new(mat.v[i][0], mat.v[i][1], mat.v[i][2], mat.v[i][3]);
+        let w = row(3);
+        let planes = [
+            w + row(0),
+            w - row(0),
+            w + row(1),
+            w - row(1),
+            w + row(2),
+            w - row(2),
+        ]
+        .map(|p| p / Vector3::new(p.v[0], p.v[1], p.v[2]).norm());
+        Self { planes }
+    }
+
+    fn distance(plane: &Vector4, p: Vector3) -> f32 {
+        plane.dot(p.homo_point())
+    }
+
+    pub fn intersects_sphere(&self, s: &Sphere) -> bool {
+        self.planes
+            .iter()
+            .all(|pl| Self::distance(pl, s.center) >= -s.radius)
+    }
+
+    //  Conservative: boxes outside the frustum but not entirely behind any
+    //  one plane still count as intersecting.
+    pub fn intersects_aabb(&self, b: &Aabb) -> bool {
+        !b.is_empty()
+            && self.planes.iter().all(|pl| {
+                //  The corner farthest along the plane normal.
+                let p = Vector3::new(
+                    if pl.v[0] >= 0. {
+                        b.max.v[0]
+                    } else {
+                        b.min.v[0]
+                    },
+                    if pl.v[1] >= 0. {
+                        b.max.v[1]
+                    } else {
+                        b.min.v[1]
+                    },
+                    if pl.v[2] >= 0. {
+                        b.max.v[2]
+                    } else {
+                        b.min.v[2]
+                    },
+                );
+                Self::distance(pl, p) >= 0.
+            })
+    }
+}
@@ -0,0 +1,358 @@
+use crate::{bounds::Aabb, linalg::Vector3, model::Model};
+
+//  Centroid bins per axis when searching for a split.
+const BINS: usize = 12;
+//  Leaves are split further while the surface area heuristic favours it,
+//  and always above this size.
+const MAX_LEAF: usize = 8;
+
+//  Interior nodes have their children at `first` and `first + 1`, leaves
+//  hold triangles `first..first + count` of `Bvh::tris`.
+#[derive(Clone, Copy, Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    first: u32,
+    count: u32,
+}
+
+//  Bounding volume hierarchy over the triangles of a model, in model space,
+//  built with the binned surface area heuristic.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    tris: Vec<u32>,
+}
+
+//  Closest intersection of a ray with a model. `bary` weights the
+//  triangle's corners and `t` is the ray parameter of the hit.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    pub triangle: usize,
+    pub bary: Vector3,
+    pub t: f32,
+}
+
+impl Bvh {
+    pub fn build(model: &Model) -> Self {
+        let corners = model
+            .indices()
+            .iter()
+            .map(|t| t.map(|i| model.vertices()[i as usize].pos))
+            .collect::<Vec<[Vector3; 3]>>();
+        let boxes = corners
+            .iter()
+            .map(|c| Aabb::from_points(*c))
+            .collect::<Vec<Aabb>>();
+        let centroids = boxes.iter().map(Aabb::center).collect::<Vec<Vector3>>();
+        let mut ret = Self {
+            nodes: vec![BvhNode {
+                bounds: Aabb::empty(),
+                first: 0,
+                count: boxes.len() as u32,
+            }],
+            tris: (0..boxes.len() as u32).collect(),
+        };
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let (first, count) = (ret.nodes[n].first as usize, ret.nodes[n].count as usize);
+            let tris = &mut ret.tris[first..first + count];
+            let bounds = tris
+                .iter()
+                .fold(Aabb::empty(), |b, &t| b.union(&boxes[t as usize]));
+            ret.nodes[n].bounds = bounds;
+            let Some((axis, lo, hi, k)) = split(tris, &boxes, &centroids, &bounds) else {
+                continue;
+            };
+
+            //  Partition by centroid bin, in place.
+            let mut mid = 0;
+            for i in 0..tris.len() {
+                if bin(centroids[tris[i] as usize].v[axis], lo, hi) <= k {
+                    tris.swap(i, mid);
+                    mid += 1;
+                }
+            }
+            if mid == 0 || mid == count {
+                continue;
+            }
+            let left = ret.nodes.len();
+            ret.nodes.push(BvhNode {
+                bounds: Aabb::empty(),
+                first: first as u32,
+                count: mid as u32,
+            });
+            ret.nodes.push(BvhNode {
+                bounds: Aabb::empty(),
+                first: (first + mid) as u32,
+                count: (count - mid) as u32,
+            });
+            ret.nodes[n].first = left as u32;
+            ret.nodes[n].count = 0;
+            stack.extend([left, left + 1]);
+        }
+        ret
+    }
+
+    //  Closest hit with `t` in (0, `t_max`), against both faces of every
+    //  triangle. `dir` need not be normalized.
+    pub fn intersect(
+        &self,
+        model: &Model,
+        origin: Vector3,
+        dir: Vector3,
+        t_max: f32,
+    ) -> Option<Hit> {
+        if self.tris.is_empty() {
+            return None;
+        }
+        let inv_dir = Vector3::new(1. / dir.v[0], 1. / dir.v[1], 1. / dir.v[2]);
+        let mut best: Option<Hit> = None;
+        let mut t_max = t_max;
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if slab(&node.bounds, origin, inv_dir, t_max).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                let (first, count) = (node.first as usize, node.count as usize);
+                for &t in &self.tris[first..first + count] {
+                    let [a, b, c] =
+                        model.indices()[t as usize].map(|i| model.vertices()[i as usize].pos);
+                    if let Some((u, v, d)) = triangle(origin, dir, a, b, c, t_max) {
+                        t_max = d;
+                        best = Some(Hit {
+                            triangle: t as usize,
+                            bary: Vector3::new(1. - u - v, u, v),
+                            t: d,
+                        });
+                    }
+                }
+                continue;
+            }
+            //  Visit the nearer child first, it is pushed last.
+            let (l, r) = (node.first as usize, node.first as usize + 1);
+            let (tl, tr) = (
+                slab(&self.nodes[l].bounds, origin, inv_dir, t_max),
+                slab(&self.nodes[r].bounds, origin, inv_dir, t_max),
+            );
+            match (tl, tr) {
+                (Some(tl), Some(tr)) if tl < tr => stack.extend([r, l]),
+                (Some(_), Some(_)) => stack.extend([l, r]),
+                (Some(_), None) => stack.push(l),
+                (None, Some(_)) => stack.push(r),
+                (None, None) => {}
+            }
+        }
+        best
+    }
+}
+
+fn bin(x: f32, lo: f32, hi: f32) -> usize {
+    (((x - lo) / (hi - lo) * BINS as f32) as usize).min(BINS - 1)
+}
+
+//  Finds the cheapest binned split of a node, as the axis, the centroid
+//  range binned along it and the last bin of the left child, or None where
+//  a leaf is cheaper.
+fn split(
+    tris: &[u32],
+    boxes: &[Aabb],
+    centroids: &[Vector3],
+    bounds: &Aabb,
+) -> Option<(usize, f32, f32, usize)> {
+    if tris.len() <= 2 {
+        return None;
+    }
+    let cb = Aabb::from_points(tris.iter().map(|&t| centroids[t as usize]));
+    let mut best: Option<(f32, usize, usize)> = None;
+    for axis in 0..3 {
+        let (lo, hi) = (cb.min.v[axis], cb.max.v[axis]);
+        if hi <= lo {
+            continue;
+        }
+        let mut bins = [(Aabb::empty(), 0usize); BINS];
+        for &t in tris {
+            let b = &mut bins[bin(centroids[t as usize].v[axis], lo, hi)];
+            b.0 = b.0.union(&boxes[t as usize]);
+            b.1 += 1;
+        }
+        //  Sweep from the right, then from the left, for the cost of each
+        //  of the BINS - 1 planes.
+        let mut right = [0.; BINS];
+        let (mut rb, mut rn) = (Aabb::empty(), 0);
+        for k in (1..BINS).rev() {
+            rb = rb.union(&bins[k].0);
+            rn += bins[k].1;
+            right[k] = rb.surface_area() * rn as f32;
+        }
+        let (mut lb, mut ln) = (Aabb::empty(), 0);
+        for k in 0..BINS - 1 {
+            lb = lb.union(&bins[k].0);
+            ln += bins[k].1;
+            if ln == 0 || ln == tris.len() {
+                continue;
+            }
+            let cost = lb.surface_area() * ln as f32 + right[k + 1];
+            if best.is_none_or(|(c, ..)| cost < c) {
+                best = Some((cost, axis, k));
+            }
+        }
+    }
+    let (cost, axis, k) = best?;
+    //  Relative to intersecting every triangle, with one traversal step
+    //  costing as much as one intersection.
+    let cost = 1. + cost / bounds.surface_area().max(f32::MIN_POSITIVE);
+    if cost >= tris.len() as f32 && tris.len() <= MAX_LEAF {
+        return None;
+    }
+    Some((axis, cb.min.v[axis], cb.max.v[axis], k))
+}
+
+//  Entry distance of the ray into the box, if it enters before `t_max`.
+fn slab(b: &Aabb, origin: Vector3, inv_dir: Vector3, t_max: f32) -> Option<f32> {
+    let (mut t0, mut t1) = (0f32, t_max);
+    for k in 0..3 {
+        //  Parallel to the slab, the origin alone decides.
+        if inv_dir.v[k].is_infinite() {
+            if origin.v[k] < b.min.v[k] || origin.v[k] > b.max.v[k] {
+                return None;
+            }
+            continue;
+        }
+        let (a, c) = (
+            (b.min.v[k] - origin.v[k]) * inv_dir.v[k],
+            (b.max.v[k] - origin.v[k]) * inv_dir.v[k],
+        );
+        t0 = t0.max(a.min(c));
+        t1 = t1.min(a.max(c));
+    }
+    (t0 <= t1).then_some(t0)
+}
+
+//  Slack on the barycentric bounds, so that rays through shared edges and
+//  vertices do not slip between triangles through rounding.
+const BARY_EPS: f32 = 1e-5;
+
+//  Möller-Trumbore, returns the barycentrics of b and c and the distance.
+fn triangle(
+    origin: Vector3,
+    dir: Vector3,
+    a: Vector3,
+    b: Vector3,
+    c: Vector3,
+    t_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let (e1, e2) = (b - a, c - a);
+    let p = dir.cross(e2);
+    let det = e1.dot(p);
+    //  `det` is at most |e1| |e2| |dir|, so the test is relative to that and
+    //  holds the same for meshes of any size: rays parallel to the plane and
+    //  degenerate triangles are rejected.
+    if det.abs() <= f32::EPSILON * e1.norm() * e2.norm() * dir.norm() {
+        return None;
+    }
+    let inv = 1. / det;
+    let s = origin - a;
+    let u = s.dot(p) * inv;
+    if !(-BARY_EPS..=1. + BARY_EPS).contains(&u) {
+        return None;
+    }
+    let q = s.cross(e1);
+    let v = dir.dot(q) * inv;
+    if v < -BARY_EPS || u + v > 1. + BARY_EPS {
+        return None;
+    }
+    let t = e2.dot(q) * inv;
+    (t > 0. && t < t_max).then_some((u, v, t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hash_unit;
+
+    //  Small random triangles in [-1, 1]^3, scaled by `scale`.
+    fn soup(scale: f32) -> Model {
+        let mut m = Model::new();
+        let mut rnd = (0u32..).map(|i| hash_unit(i) * 2. - 1.);
+        let mut point = || {
+            Vector3::new(
+                rnd.next().unwrap(),
+                rnd.next().unwrap(),
+                rnd.next().unwrap(),
+            )
+        };
+        for _ in 0..200 {
+            let c = point();
+            let [a, b, d] = [0; 3].map(|_| {
+                let p = c + point() * 0.2;
+                m.add_vertex(p * scale, None, None)
+            });
+            m.add_tri(a, b, d);
+        }
+        m
+    }
+
+    //  Rays from within [-2, 2]^3, scaled by `scale`.
+    fn rays(scale: f32) -> impl Iterator<Item = (Vector3, Vector3)> {
+        let mut rnd = (1_000_000u32..).map(|i| hash_unit(i) * 2. - 1.);
+        let mut point = move || {
+            Vector3::new(
+                rnd.next().unwrap(),
+                rnd.next().unwrap(),
+                rnd.next().unwrap(),
+            )
+        };
+        (0..2000).map(move |_| (point() * 2. * scale, point()))
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let m = soup(1.);
+        let bvh = Bvh::build(&m);
+        let mut hits = 0;
+        for (origin, dir) in rays(1.) {
+            let brute = m
+                .indices()
+                .iter()
+                .enumerate()
+                .filter_map(|(i, t)| {
+                    let [a, b, c] = t.map(|k| m.vertices()[k as usize].pos);
+                    triangle(origin, dir, a, b, c, f32::INFINITY).map(|(.., d)| (i, d))
+                })
+                .min_by(|x, y| x.1.total_cmp(&y.1));
+            let hit = bvh.intersect(&m, origin, dir, f32::INFINITY);
+            match (brute, hit) {
+                (None, None) => {}
+                (Some((_, d)), Some(h)) => {
+                    assert!((d - h.t).abs() <= 1e-5 * d.max(1.));
+                    hits += 1;
+                }
+                (b, h) => panic!("brute force {:?}, bvh {:?}", b, h),
+            }
+        }
+        assert!(hits > 100, "{} hits", hits);
+    }
+
+    //  Scaling by a power of two is exact, so very small and very large
+    //  meshes have to be hit exactly where the unit sized one is.
+    #[test]
+    fn hits_do_not_depend_on_scale() {
+        let hits = |scale: f32| {
+            let m = soup(scale);
+            let bvh = Bvh::build(&m);
+            rays(scale)
+                .map(|(origin, dir)| {
+                    bvh.intersect(&m, origin, dir, f32::INFINITY)
+                        .map(|h| (h.triangle, h.t / scale))
+                })
+                .collect::<Vec<_>>()
+        };
+        let unit = hits(1.);
+        assert!(unit.iter().flatten().count() > 100);
+        for scale in [2f32.powi(-24), 2f32.powi(24)] {
+            assert_eq!(hits(scale), unit, "scale {}", scale);
+        }
+    }
+}
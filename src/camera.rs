@@ -1,9 +1,11 @@
+use crate::bounds::Frustum;
 use crate::linalg::{Matrix4, Vector3};
 use crate::utils::EPS;
 
 const ZNR: f32 = 0.01;
 const ZFR: f32 = 500.;
 
+#[derive(Clone, Debug)]
 pub struct Camera {
     pub pos: Vector3,
     pub dir: Vector3,
@@ -23,6 +25,47 @@ impl Camera {
             aspect,
         }
     }
+    //  Aims the camera at `target`, `up` only needs to be roughly upwards and
+    //  is re-orthogonalized against the view direction.
+    pub fn look_at(pos: Vector3, target: Vector3, up: Vector3, fov_y: f32, aspect: f32) -> Self {
+        let mut ret = Self {
+            pos,
+            dir: Vector3::new(0., 0., -1.),
+            up: Vector3::new(0., 1., 0.),
+            fov_y,
+            aspect,
+        };
+        ret.aim(target, up);
+        ret
+    }
+    //  A target at the camera position keeps the current direction. When
+    //  `up` is parallel to the view, the previous up is used instead, then
+    //  the world axis least aligned with the view.
+    pub fn aim(&mut self, target: Vector3, up: Vector3) {
+        let dir = target - self.pos;
+        if dir.norm() > EPS {
+            self.dir = dir.normalize();
+        }
+        let k = (0..3)
+            .min_by(|&a, &b| self.dir.v[a].abs().total_cmp(&self.dir.v[b].abs()))
+            .unwrap();
+        let mut axis = Vector3::new(0., 0., 0.);
+        axis.v[k] = 1.;
+        for u in [up, self.up, axis] {
+            let u = u.normalize_or_zero();
+            let u = u - self.dir * u.dot(self.dir);
+            if u.norm() > EPS {
+                self.up = u.normalize();
+                return;
+            }
+        }
+    }
+    //  World-space view ray through normalized device coordinates.
+    pub fn ray_dir(&self, ndc_x: f32, ndc_y: f32) -> Vector3 {
+        let ty = (self.fov_y / 2.).tan();
+        let right = self.dir.cross(self.up);
+        (self.dir + right * (ndc_x * ty * self.aspect) + self.up * (ndc_y * ty)).normalize()
+    }
     pub fn camera_transform(&self) -> Matrix4 {
         assert!(self.dir.dot(self.up).abs() < EPS);
         let v = self.dir.cross(self.up);
@@ -57,4 +100,50 @@ impl Camera {
             ],
         }
     }
+    //  World-space view volume, between the near and far planes.
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.perspective_transform() * self.camera_transform())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_frame(cam: &Camera) {
+        assert!((cam.dir.norm() - 1.).abs() < 1e-5);
+        assert!((cam.up.norm() - 1.).abs() < 1e-5);
+        assert!(cam.dir.dot(cam.up).abs() < EPS);
+    }
+
+    #[test]
+    fn look_at_straight_down() {
+        let up = Vector3::new(0., 1., 0.);
+        let cam = Camera::look_at(
+            Vector3::new(0., 5., 0.),
+            Vector3::new(0., 0., 0.),
+            up,
+            1.,
+            1.,
+        );
+        assert_frame(&cam);
+        assert!((cam.dir - Vector3::new(0., -1., 0.)).norm() < 1e-5);
+        cam.camera_transform();
+    }
+
+    #[test]
+    fn aim_at_own_position_keeps_direction() {
+        let up = Vector3::new(0., 1., 0.);
+        let mut cam = Camera::look_at(
+            Vector3::new(0., 0., 5.),
+            Vector3::new(0., 0., 0.),
+            up,
+            1.,
+            1.,
+        );
+        let dir = cam.dir;
+        cam.aim(cam.pos, up);
+        assert_frame(&cam);
+        assert!((cam.dir - dir).norm() < 1e-6);
+    }
 }
@@ -0,0 +1,336 @@
+use crate::{linalg::Vector3, scene::Scene};
+use image::RgbImage;
+use std::{error::Error, f32::consts::PI, fs::File, io::BufWriter, path::Path};
+
+#[derive(Clone, Copy, Debug)]
+pub struct GifOptions {
+    //  Palette size, at most 256.
+    pub colors: usize,
+    pub dither: bool,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        Self {
+            colors: 256,
+            dither: true,
+        }
+    }
+}
+
+//  Frames share one median-cut palette built from all of them, so colors do
+//  not flicker between frames.
+pub fn save_gif<P>(
+    path: P,
+    frames: &[RgbImage],
+    fps: f32,
+    opts: GifOptions,
+) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let (w, h) = check_frames(frames, fps)?;
+    if !(1..=256).contains(&opts.colors) {
+        return Err("gif: palette size must be in 1..=256".into());
+    }
+    if w > u16::MAX as u32 || h > u16::MAX as u32 {
+        return Err("gif: frames too large".into());
+    }
+    let palette = median_cut(frames, opts.colors);
+    let lut = NearestLut::new(&palette);
+    let mut flat = palette.iter().flatten().copied().collect::<Vec<u8>>();
+    //  GIF palettes must have a power of two size.
+    flat.resize(3 * palette.len().next_power_of_two().max(2), 0);
+
+    let mut enc = gif::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        w as u16,
+        h as u16,
+        &flat,
+    )?;
+    enc.set_repeat(gif::Repeat::Infinite)?;
+    let delay = (100. / fps).round() as u16;
+    for img in frames {
+        let idx = if opts.dither {
+            dither(img, &palette, &lut)
+        } else {
+            img.pixels().map(|p| lut.nearest(p.0)).collect()
+        };
+        let mut frame = gif::Frame::from_indexed_pixels(w as u16, h as u16, idx, None);
+        frame.delay = delay;
+        enc.write_frame(&frame)?;
+    }
+    Ok(())
+}
+
+pub fn save_apng<P>(path: P, frames: &[RgbImage], fps: f32) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let (w, h) = check_frames(frames, fps)?;
+    let mut enc = png::Encoder::new(BufWriter::new(File::create(path)?), w, h);
+    enc.set_color(png::ColorType::Rgb);
+    enc.set_depth(png::BitDepth::Eight);
+    enc.set_animated(frames.len() as u32, 0)?;
+    enc.set_frame_delay((1000. / fps).round() as u16, 1000)?;
+    let mut writer = enc.write_header()?;
+    for img in frames {
+        writer.write_image_data(img.as_raw())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+//  Dimensions shared by all frames, which must be at least one.
+fn check_frames(frames: &[RgbImage], fps: f32) -> Result<(u32, u32), Box<dyn Error>> {
+    let first = frames.first().ok_or("encode: no frames")?;
+    if frames.iter().any(|f| f.dimensions() != first.dimensions()) {
+        return Err("encode: frames differ in size".into());
+    }
+    //  Both formats store delays of at most 65535 units.
+    if !(1000. / 65535. ..=1000.).contains(&fps) {
+        return Err("encode: frame rate out of range".into());
+    }
+    Ok(first.dimensions())
+}
+
+//  Orbits the camera around `target` about the world y axis, keeping the
+//  current distance and height, for one full revolution.
+#[derive(Clone, Copy, Debug)]
+pub struct Turntable {
+    pub target: Vector3,
+    pub frames: usize,
+    pub width: usize,
+    pub height: usize,
+    pub msaa: usize,
+}
+
+impl Turntable {
+    pub fn render(&self, scene: &mut Scene) -> Result<Vec<RgbImage>, Box<dyn Error>> {
+        if self.frames == 0 {
+            return Err("turntable: no frames".into());
+        }
+        let saved = scene.camera().clone();
+        let off = saved.pos - self.target;
+        let up = Vector3::new(0., 1., 0.);
+        let mut ret = vec![];
+        for i in 0..self.frames {
+            let ang = 2. * PI * i as f32 / self.frames as f32;
+            let (s, c) = ang.sin_cos();
+            let cam = scene.camera_mut();
+            cam.pos = self.target
+                + Vector3::new(
+                    c * off.v[0] + s * off.v[2],
+                    off.v[1],
+                    c * off.v[2] - s * off.v[0],
+                );
+            cam.aim(self.target, up);
+            let buf = scene.rasterize(self.width, self.height, self.msaa);
+            ret.push(RgbImage::from_raw(self.width as u32, self.height as u32, buf).unwrap());
+        }
+        *scene.camera_mut() = saved;
+        Ok(ret)
+    }
+
+    pub fn save_gif<P>(
+        &self,
+        scene: &mut Scene,
+        path: P,
+        fps: f32,
+        opts: GifOptions,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        save_gif(path, &self.render(scene)?, fps, opts)
+    }
+
+    pub fn save_apng<P>(&self, scene: &mut Scene, path: P, fps: f32) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        save_apng(path, &self.render(scene)?, fps)
+    }
+}
+
+fn median_cut(frames: &[RgbImage], colors: usize) -> Vec<[u8; 3]> {
+    //  Subsample to keep the sort cheap on long sequences.
+    let total = frames.iter().map(|f| f.len() / 3).sum::<usize>();
+    let step = (total / 65536).max(1);
+    let px = frames
+        .iter()
+        .flat_map(|f| f.pixels().map(|p| p.0))
+        .step_by(step)
+        .collect::<Vec<[u8; 3]>>();
+
+    let range = |b: &[[u8; 3]]| {
+        let mut r = [(0usize, 0u8); 3];
+        for (c, rc) in r.iter_mut().enumerate() {
+            let (lo, hi) = b
+                .iter()
+                .fold((255, 0), |(lo, hi), p| (p[c].min(lo), p[c].max(hi)));
+            *rc = (c, hi - lo);
+        }
+        r.into_iter().max_by_key(|&(_, d)| d).unwrap()
+    };
+    let mut boxes = vec![px];
+    while boxes.len() < colors {
+        let Some((i, (c, _))) = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (i, range(b)))
+            .filter(|(_, (_, d))| *d > 0)
+            .max_by_key(|(i, (_, d))| *d as usize * boxes[*i].len())
+        else {
+            break;
+        };
+        let mut b = boxes.swap_remove(i);
+        b.sort_unstable_by_key(|p| p[c]);
+        let hi = b.split_off(b.len() / 2);
+        boxes.push(b);
+        boxes.push(hi);
+    }
+    boxes
+        .iter()
+        .filter(|b| !b.is_empty())
+        .map(|b| {
+            let mut sum = [0usize; 3];
+            for p in b {
+                for c in 0..3 {
+                    sum[c] += p[c] as usize;
+                }
+            }
+            sum.map(|s| (s / b.len()) as u8)
+        })
+        .collect()
+}
+
+//  Nearest palette entry for every color quantized to 5 bits per channel.
+struct NearestLut {
+    idx: Vec<u8>,
+}
+
+impl NearestLut {
+    fn new(palette: &[[u8; 3]]) -> Self {
+        let idx = (0..32 * 32 * 32)
+            .map(|i| {
+                let c = [(i >> 10) & 31, (i >> 5) & 31, i & 31].map(|x| (x * 8 + 4) as f32);
+                Self::search(palette, c)
+            })
+            .collect();
+        Self { idx }
+    }
+
+    fn search(palette: &[[u8; 3]], c: [f32; 3]) -> u8 {
+        (0..palette.len())
+            .min_by(|&a, &b| {
+                let d = |p: [u8; 3]| (0..3).map(|k| (p[k] as f32 - c[k]).powi(2)).sum::<f32>();
+                d(palette[a]).total_cmp(&d(palette[b]))
+            })
+            .unwrap() as u8
+    }
+
+    fn nearest(&self, c: [u8; 3]) -> u8 {
+        let [r, g, b] = c.map(|x| (x >> 3) as usize);
+        self.idx[(r << 10) | (g << 5) | b]
+    }
+}
+
+//  Floyd-Steinberg error diffusion.
+fn dither(img: &RgbImage, palette: &[[u8; 3]], lut: &NearestLut) -> Vec<u8> {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let mut buf = img.as_raw().iter().map(|&x| x as f32).collect::<Vec<f32>>();
+    let mut ret = vec![0u8; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let i = y * w + x;
+            let c = [0, 1, 2].map(|k| buf[i * 3 + k].clamp(0., 255.));
+            let pi = lut.nearest(c.map(|v| v as u8));
+            ret[i] = pi;
+            let err = [0, 1, 2].map(|k| c[k] - palette[pi as usize][k] as f32);
+            let mut spread = |dx: isize, dy: usize, f: f32| {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx >= 0 && (nx as usize) < w && ny < h {
+                    let j = ny * w + nx as usize;
+                    for k in 0..3 {
+                        buf[j * 3 + k] += err[k] * f;
+                    }
+                }
+            };
+            spread(1, 0, 7. / 16.);
+            spread(-1, 1, 3. / 16.);
+            spread(0, 1, 5. / 16.);
+            spread(1, 1, 1. / 16.);
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn frames(n: usize) -> Vec<RgbImage> {
+        (0..n)
+            .map(|i| RgbImage::from_pixel(4, 3, Rgb([(i * 40) as u8, 0, 255])))
+            .collect()
+    }
+
+    #[test]
+    fn gif_frames_and_delay() {
+        let path = std::env::temp_dir().join("tinyrenderer_rs_encode_test.gif");
+        save_gif(&path, &frames(3), 25., GifOptions::default()).unwrap();
+        let mut dec = gif::DecodeOptions::new()
+            .read_info(File::open(&path).unwrap())
+            .unwrap();
+        let mut delays = vec![];
+        while let Some(f) = dec.read_next_frame().unwrap() {
+            delays.push(f.delay);
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(delays, [4, 4, 4]);
+    }
+
+    #[test]
+    fn apng_frames_and_delay() {
+        let path = std::env::temp_dir().join("tinyrenderer_rs_encode_test.png");
+        save_apng(&path, &frames(3), 120.).unwrap();
+        let mut reader = png::Decoder::new(File::open(&path).unwrap())
+            .read_info()
+            .unwrap();
+        assert_eq!(reader.info().animation_control.unwrap().num_frames, 3);
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let mut delays = vec![];
+        while reader.next_frame(&mut buf).is_ok() {
+            let fc = reader.info().frame_control.unwrap();
+            delays.push((fc.delay_num, fc.delay_den));
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(delays, [(8, 1000); 3]);
+    }
+
+    #[test]
+    fn rejects_empty_and_bad_input() {
+        let path = std::env::temp_dir().join("tinyrenderer_rs_encode_empty.gif");
+        assert!(save_gif(&path, &[], 25., GifOptions::default()).is_err());
+        assert!(save_apng(&path, &[], 25.).is_err());
+        let opts = GifOptions {
+            colors: 0,
+            ..GifOptions::default()
+        };
+        assert!(save_gif(&path, &frames(1), 25., opts).is_err());
+        let mut mixed = frames(1);
+        mixed.push(RgbImage::new(2, 2));
+        assert!(save_apng(&path, &mixed, 25.).is_err());
+        let tt = Turntable {
+            target: Vector3::new(0., 0., 0.),
+            frames: 0,
+            width: 4,
+            height: 4,
+            msaa: 1,
+        };
+        assert!(tt.render(&mut Scene::new()).is_err());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,305 @@
+use crate::linalg::{Vector2, Vector3};
+use image::{ImageResult, Rgb32FImage};
+use std::{f32::consts::PI, path::Path};
+
+const SPEC_LEVELS: usize = 6;
+const SPEC_SAMPLES: usize = 64;
+const SPEC_WIDTH: usize = 128;
+const LUT_SIZE: usize = 32;
+const LUT_SAMPLES: usize = 128;
+
+//  Linear HDR image in equirectangular layout, u follows the azimuth
+//  around +y and v runs from +y (top row) to -y.
+#[derive(Clone, Debug)]
+pub struct EquirectMap {
+    width: usize,
+    height: usize,
+    data: Vec<Vector3>,
+}
+
+impl EquirectMap {
+    pub fn new(img: &Rgb32FImage) -> Self {
+        Self {
+            width: img.width() as usize,
+            height: img.height() as usize,
+            data: img
+                .pixels()
+                .map(|p| Vector3::new(p.0[0], p.0[1], p.0[2]))
+                .collect(),
+        }
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn dir_to_uv(d: Vector3) -> Vector2 {
+        let d = d.normalize();
+        Vector2::new(
+            0.5 + d.v[0].atan2(-d.v[2]) / (2. * PI),
+            d.v[1].clamp(-1., 1.).acos() / PI,
+        )
+    }
+
+    pub fn uv_to_dir(uv: Vector2) -> Vector3 {
+        let (phi, theta) = ((uv.v[0] - 0.5) * 2. * PI, uv.v[1] * PI);
+        Vector3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+
+    fn texel(&self, x: isize, y: isize) -> Vector3 {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.data[y * self.width + x]
+    }
+
+    //  Bilinear, wrapping horizontally.
+    pub fn at_uv(&self, uv: Vector2) -> Vector3 {
+        let x = uv.v[0] * self.width as f32 - 0.5;
+        let y = uv.v[1] * self.height as f32 - 0.5;
+        let (xf, yf) = (x.floor(), y.floor());
+        let (s, t) = (x - xf, y - yf);
+        let (xi, yi) = (xf as isize, yf as isize);
+        (self.texel(xi, yi) * (1. - s) + self.texel(xi + 1, yi) * s) * (1. - t)
+            + (self.texel(xi, yi + 1) * (1. - s) + self.texel(xi + 1, yi + 1) * s) * t
+    }
+
+    pub fn at_dir(&self, d: Vector3) -> Vector3 {
+        self.at_uv(Self::dir_to_uv(d))
+    }
+
+    //  Box filter to half size, used to build the source mip chain.
+    fn downsample(&self) -> Self {
+        let (w, h) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let mut data = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                let (x0, y0) = (x * 2, y * 2);
+                let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+                let at = |x: usize, y: usize| self.data[y * self.width + x];
+                data.push((at(x0, y0) + at(x1, y0) + at(x0, y1) + at(x1, y1)) * 0.25);
+            }
+        }
+        Self {
+            width: w,
+            height: h,
+            data,
+        }
+    }
+
+    fn resized(&self, width: usize) -> Self {
+        let mut ret = self.clone();
+        while ret.width / 2 >= width {
+            ret = ret.downsample();
+        }
+        ret
+    }
+}
+
+//  Equirectangular environment with precomputed image-based lighting:
+//  order-2 spherical harmonics of the diffuse irradiance, GGX-prefiltered
+//  radiance for a range of roughness levels and the split-sum BRDF table.
+#[derive(Clone, Debug)]
+pub struct Environment {
+    pub map: EquirectMap,
+    pub intensity: f32,
+    sh: [Vector3; 9],
+    specular: Vec<EquirectMap>,
+    brdf_lut: Vec<Vector2>,
+}
+
+impl Environment {
+    pub fn open<P>(path: P) -> ImageResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self::new(EquirectMap::new(
+            &image::open(path)?.into_rgb32f(),
+        )))
+    }
+
+    pub fn new(map: EquirectMap) -> Self {
+        let sh = project_sh(&map.resized(64));
+        let specular = prefilter_specular(&map);
+        Self {
+            map,
+            intensity: 1.,
+            sh,
+            specular,
+            brdf_lut: integrate_brdf(),
+        }
+    }
+
+    pub fn radiance(&self, d: Vector3) -> Vector3 {
+        self.map.at_dir(d) * self.intensity
+    }
+
+    //  Cosine-weighted irradiance around the normal `n`.
+    pub fn irradiance(&self, n: Vector3) -> Vector3 {
+        const A: [f32; 9] = [
+            PI,
+            2. * PI / 3.,
+            2. * PI / 3.,
+            2. * PI / 3.,
+            PI / 4.,
+            PI / 4.,
+            PI / 4.,
+            PI / 4.,
+            PI / 4.,
+        ];
+        let y = sh_basis(n);
+        let mut ret = Vector3::new(0., 0., 0.);
+        for i in 0..9 {
+            ret += self.sh[i] * (A[i] * y[i]);
+        }
+        Vector3::new(ret.v[0].max(0.), ret.v[1].max(0.), ret.v[2].max(0.)) * self.intensity
+    }
+
+    //  Radiance around the reflection vector `r` prefiltered for `roughness`.
+    pub fn prefiltered(&self, r: Vector3, roughness: f32) -> Vector3 {
+        let lv = roughness.clamp(0., 1.) * (SPEC_LEVELS - 1) as f32;
+        let (l0, t) = (lv.floor() as usize, lv.fract());
+        let l1 = (l0 + 1).min(SPEC_LEVELS - 1);
+        let uv = EquirectMap::dir_to_uv(r);
+        (self.specular[l0].at_uv(uv) * (1. - t) + self.specular[l1].at_uv(uv) * t) * self.intensity
+    }
+
+    //  Split-sum scale and bias applied to F0.
+    pub fn brdf(&self, n_dot_v: f32, roughness: f32) -> Vector2 {
+        let x = (n_dot_v.clamp(0., 1.) * (LUT_SIZE - 1) as f32).round() as usize;
+        let y = (roughness.clamp(0., 1.) * (LUT_SIZE - 1) as f32).round() as usize;
+        self.brdf_lut[y * LUT_SIZE + x]
+    }
+}
+
+fn sh_basis(d: Vector3) -> [f32; 9] {
+    let [x, y, z] = d.v;
+    [
+        0.282095,
+        0.488603 * y,
+        0.488603 * z,
+        0.488603 * x,
+        1.092548 * x * y,
+        1.092548 * y * z,
+        0.315392 * (3. * z * z - 1.),
+        1.092548 * x * z,
+        0.546274 * (x * x - y * y),
+    ]
+}
+
+fn project_sh(map: &EquirectMap) -> [Vector3; 9] {
+    let (w, h) = map.dimensions();
+    let mut sh = [Vector3::new(0., 0., 0.); 9];
+    for y in 0..h {
+        let theta = (y as f32 + 0.5) / h as f32 * PI;
+        //  Solid angle of one texel.
+        let dw = (2. * PI / w as f32) * (PI / h as f32) * theta.sin();
+        for x in 0..w {
+            let uv = Vector2::new((x as f32 + 0.5) / w as f32, (y as f32 + 0.5) / h as f32);
+            let basis = sh_basis(EquirectMap::uv_to_dir(uv));
+            let c = map.data[y * w + x] * dw;
+            for i in 0..9 {
+                sh[i] += c * basis[i];
+            }
+        }
+    }
+    sh
+}
+
+fn hammersley(i: usize, n: usize) -> Vector2 {
+    Vector2::new(
+        i as f32 / n as f32,
+        (i as u32).reverse_bits() as f32 / 4294967296.,
+    )
+}
+
+//  GGX half vector around `n` for the sample `xi`.
+fn importance_ggx(xi: Vector2, n: Vector3, roughness: f32) -> Vector3 {
+    let a = roughness * roughness;
+    let phi = 2. * PI * xi.v[0];
+    let cos_t = ((1. - xi.v[1]) / (1. + (a * a - 1.) * xi.v[1])).sqrt();
+    let sin_t = (1. - cos_t * cos_t).max(0.).sqrt();
+    let up = if n.v[1].abs() < 0.999 {
+        Vector3::new(0., 1., 0.)
+    } else {
+        Vector3::new(1., 0., 0.)
+    };
+    let t = up.cross(n).normalize();
+    let b = n.cross(t);
+    (t * (sin_t * phi.cos()) + b * (sin_t * phi.sin()) + n * cos_t).normalize()
+}
+
+//  Each level halves the resolution and samples a correspondingly blurred
+//  source, which keeps a fixed sample count free of fireflies.
+fn prefilter_specular(map: &EquirectMap) -> Vec<EquirectMap> {
+    let mut src = vec![map.resized(SPEC_WIDTH)];
+    for _ in 1..SPEC_LEVELS {
+        let next = src.last().unwrap().downsample();
+        src.push(next);
+    }
+    let mut ret = vec![src[0].clone()];
+    for (level, source) in src.iter().enumerate().skip(1) {
+        let roughness = level as f32 / (SPEC_LEVELS - 1) as f32;
+        let (w, h) = (
+            (SPEC_WIDTH >> level).max(8),
+            (SPEC_WIDTH >> (level + 1)).max(4),
+        );
+        let mut data = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                let uv = Vector2::new((x as f32 + 0.5) / w as f32, (y as f32 + 0.5) / h as f32);
+                let n = EquirectMap::uv_to_dir(uv);
+                let (mut sum, mut weight) = (Vector3::new(0., 0., 0.), 0.);
+                for i in 0..SPEC_SAMPLES {
+                    let hv = importance_ggx(hammersley(i, SPEC_SAMPLES), n, roughness);
+                    let l = hv * (2. * n.dot(hv)) - n;
+                    let nl = n.dot(l);
+                    if nl > 0. {
+                        sum += source.at_dir(l) * nl;
+                        weight += nl;
+                    }
+                }
+                data.push(sum / weight.max(1e-6));
+            }
+        }
+        ret.push(EquirectMap {
+            width: w,
+            height: h,
+            data,
+        });
+    }
+    ret
+}
+
+fn integrate_brdf() -> Vec<Vector2> {
+    let mut ret = Vec::with_capacity(LUT_SIZE * LUT_SIZE);
+    let n = Vector3::new(0., 0., 1.);
+    for y in 0..LUT_SIZE {
+        let roughness = (y as f32 / (LUT_SIZE - 1) as f32).max(0.02);
+        let a = roughness * roughness;
+        for x in 0..LUT_SIZE {
+            let nv = (x as f32 / (LUT_SIZE - 1) as f32).max(1e-3);
+            let v = Vector3::new((1. - nv * nv).sqrt(), 0., nv);
+            let (mut sa, mut sb) = (0., 0.);
+            for i in 0..LUT_SAMPLES {
+                let hv = importance_ggx(hammersley(i, LUT_SAMPLES), n, roughness);
+                let l = hv * (2. * v.dot(hv)) - v;
+                let (nl, nh, vh) = (l.v[2].max(0.), hv.v[2].max(0.), v.dot(hv).max(0.));
+                if nl > 0. {
+                    //  Smith-Schlick visibility with the IBL remapping of k.
+                    let k = a / 2.;
+                    let g = nv / (nv * (1. - k) + k) * nl / (nl * (1. - k) + k);
+                    let g_vis = g * vh / (nh * nv).max(1e-6);
+                    let fc = (1. - vh).powi(5);
+                    sa += (1. - fc) * g_vis;
+                    sb += fc * g_vis;
+                }
+            }
+            ret.push(Vector2::new(sa, sb) / LUT_SAMPLES as f32);
+        }
+    }
+    ret
+}
@@ -0,0 +1,269 @@
+use crate::{
+    linalg::{Matrix3, Matrix4},
+    material::Material,
+    model::{Model, Vertex},
+    texture::Texture,
+};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    rc::Rc,
+};
+
+//  A mesh with the transform to bake into it and the material to write.
+pub type Part<'a> = (&'a Model, Matrix4, &'a Material);
+
+//  Whether `mat` mirrors, so that faces need their winding reversed to
+//  stay counter-clockwise about the transformed normals.
+fn mirrors(mat: Matrix4) -> bool {
+    Matrix3::from_mat4(&mat).determinant() < 0.
+}
+
+fn wind(tri: [u32; 3], flip: bool) -> [u32; 3] {
+    if flip {
+        [tri[0], tri[2], tri[1]]
+    } else {
+        tri
+    }
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map_or("model".into(), |s| s.to_string_lossy().into_owned())
+}
+
+//  Writes the parts as groups of one OBJ, with positions and normals
+//  transformed, and an MTL file of the same name beside it. Textures are
+//  saved as PNG next to the MTL. Vertex colors, when present, follow the
+//  position as in `v x y z r g b`.
+pub fn save_obj<P>(path: P, parts: &[Part]) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let name = stem(path);
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "mtllib {}.mtl", name)?;
+
+    //  Parts sharing a material share its MTL entry.
+    let mut mtls: Vec<&Material> = vec![];
+    let mut n = 1;
+    for (k, &(model, mat, material)) in parts.iter().enumerate() {
+        let m = match mtls.iter().position(|&m| std::ptr::eq(m, material)) {
+            Some(m) => m,
+            None => {
+                mtls.push(material);
+                mtls.len() - 1
+            }
+        };
+        writeln!(out, "o part{}", k)?;
+        writeln!(out, "usemtl material{}", m)?;
+        let nmat = mat.normal_matrix();
+        for v in &model.vertices {
+            let p = (mat * v.pos.homo_point()).vec3_homo();
+            if model.has_color {
+                let c = v.color;
+                writeln!(
+                    out,
+                    "v {} {} {} {} {} {}",
+                    p.v[0], p.v[1], p.v[2], c.v[0], c.v[1], c.v[2]
+                )?;
+            } else {
+                writeln!(out, "v {} {} {}", p.v[0], p.v[1], p.v[2])?;
+            }
+        }
+        if model.has_uv {
+            for v in &model.vertices {
+                writeln!(out, "vt {} {}", v.uv.v[0], v.uv.v[1])?;
+            }
+        }
+        if model.has_norm {
+            for v in &model.vertices {
+                let n = (nmat * v.norm).normalize_or_zero();
+                writeln!(out, "vn {} {} {}", n.v[0], n.v[1], n.v[2])?;
+            }
+        }
+        //  Every attribute is written per vertex, so all share one index.
+        let flip = mirrors(mat);
+        for &tri in &model.indices {
+            write!(out, "f")?;
+            for i in wind(tri, flip) {
+                let i = i as usize + n;
+                match (model.has_uv, model.has_norm) {
+                    (true, true) => write!(out, " {}/{}/{}", i, i, i)?,
+                    (true, false) => write!(out, " {}/{}", i, i)?,
+                    (false, true) => write!(out, " {}//{}", i, i)?,
+                    (false, false) => write!(out, " {}", i)?,
+                }
+            }
+            writeln!(out)?;
+        }
+        n += model.vertices.len();
+    }
+    out.flush()?;
+
+    let mut out = BufWriter::new(File::create(dir.join(format!("{}.mtl", name)))?);
+    let mut textures: Vec<(Rc<Texture>, String)> = vec![];
+    let mut texture = |t: &Rc<Texture>| -> Result<String, Box<dyn Error>> {
+        if let Some((_, f)) = textures.iter().find(|(u, _)| Rc::ptr_eq(u, t)) {
+            return Ok(f.clone());
+        }
+        let f = format!("{}_tex{}.png", name, textures.len());
+        t.save(dir.join(&f))?;
+        textures.push((t.clone(), f.clone()));
+        Ok(f)
+    };
+    for (m, material) in mtls.iter().enumerate() {
+        let e = material.emissive * material.emissive_strength;
+        let [r, g, b] = material.base_color.v;
+        let [sr, sg, sb] = material.specular.v;
+        writeln!(out, "newmtl material{}", m)?;
+        writeln!(out, "Kd {} {} {}", r, g, b)?;
+        writeln!(out, "Ks {} {} {}", sr, sg, sb)?;
+        writeln!(out, "Ns {}", material.shininess)?;
+        writeln!(out, "Ke {} {} {}", e.v[0], e.v[1], e.v[2])?;
+        //  PBR extension of the MTL format.
+        writeln!(out, "Pr {}", material.roughness)?;
+        writeln!(out, "Pm {}", material.metallic)?;
+        if let Some(t) = &material.texture {
+            writeln!(out, "map_Kd {}", texture(t)?)?;
+        }
+        if let Some(t) = &material.emissive_map {
+            writeln!(out, "map_Ke {}", texture(t)?)?;
+        }
+        writeln!(out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+//  Writes the parts merged into one binary little-endian PLY. Materials
+//  are not stored.
+pub fn save_ply<P>(path: P, parts: &[Part]) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let has_uv = parts.iter().any(|(m, ..)| m.has_uv);
+    let has_norm = parts.iter().any(|(m, ..)| m.has_norm);
+    let has_color = parts.iter().any(|(m, ..)| m.has_color);
+
+    let mut verts: Vec<Vertex> = vec![];
+    let mut faces = vec![];
+    for &(model, mat, _) in parts {
+        let nmat = mat.normal_matrix();
+        let base = verts.len() as u32;
+        verts.extend(model.vertices.iter().map(|v| Vertex {
+            pos: (mat * v.pos.homo_point()).vec3_homo(),
+            norm: (nmat * v.norm).normalize_or_zero(),
+            ..*v
+        }));
+        let flip = mirrors(mat);
+        faces.extend(
+            model
+                .indices
+                .iter()
+                .map(|&t| wind(t, flip).map(|i| i + base)),
+        );
+    }
+
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "ply")?;
+    writeln!(out, "format binary_little_endian 1.0")?;
+    writeln!(out, "element vertex {}", verts.len())?;
+    for p in ["x", "y", "z"] {
+        writeln!(out, "property float {}", p)?;
+    }
+    if has_norm {
+        for p in ["nx", "ny", "nz"] {
+            writeln!(out, "property float {}", p)?;
+        }
+    }
+    if has_uv {
+        for p in ["s", "t"] {
+            writeln!(out, "property float {}", p)?;
+        }
+    }
+    if has_color {
+        for p in ["red", "green", "blue"] {
+            writeln!(out, "property uchar {}", p)?;
+        }
+    }
+    writeln!(out, "element face {}", faces.len())?;
+    writeln!(out, "property list uchar uint vertex_indices")?;
+    writeln!(out, "end_header")?;
+    for v in &verts {
+        let mut f = v.pos.v.to_vec();
+        if has_norm {
+            f.extend_from_slice(&v.norm.v);
+        }
+        if has_uv {
+            f.extend_from_slice(&v.uv.v);
+        }
+        for x in f {
+            out.write_all(&x.to_le_bytes())?;
+        }
+        if has_color {
+            out.write_all(&v.color.v.map(|x| (x.clamp(0., 1.) * 255.).round() as u8))?;
+        }
+    }
+    for face in &faces {
+        out.write_all(&[3])?;
+        for i in face {
+            out.write_all(&i.to_le_bytes())?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linalg::{transform::Transform, Vector3};
+
+    //  Every face winds counter-clockwise about its vertex normals.
+    fn assert_consistent(m: &Model) {
+        assert!(m.tri_count() > 0);
+        for t in m.indices() {
+            let [a, b, c] = t.map(|i| m.vertices()[i as usize]);
+            let face = (b.pos - a.pos).cross(c.pos - a.pos);
+            for v in [a, b, c] {
+                assert!(face.dot(v.norm) > 0.);
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored_parts_keep_winding() {
+        let mut m = Model::new();
+        let n = Some(Vector3::new(0., 0., 1.));
+        let [a, b, c] = [(0., 0.), (1., 0.), (0., 1.)]
+            .map(|(x, y)| m.add_vertex(Vector3::new(x, y, 0.), None, n));
+        m.add_tri(a, b, c);
+        let mtl = Material::new();
+        let mirror = Transform::new()
+            .scale(Vector3::new(-1., 1., 1.))
+            .translation(Vector3::new(3., 0., 0.))
+            .mat();
+        let parts = [(&m, Matrix4::identity(), &mtl), (&m, mirror, &mtl)];
+
+        let dir = std::env::temp_dir();
+        let obj = dir.join("tinyrenderer_rs_export_test.obj");
+        save_obj(&obj, &parts).unwrap();
+        let from_obj = Model::open(&obj).unwrap();
+        std::fs::remove_file(&obj).unwrap();
+        std::fs::remove_file(obj.with_extension("mtl")).unwrap();
+        assert_eq!(from_obj.tri_count(), 2);
+        assert_consistent(&from_obj);
+
+        let ply = dir.join("tinyrenderer_rs_export_test.ply");
+        save_ply(&ply, &parts).unwrap();
+        let from_ply = Model::open(&ply).unwrap();
+        std::fs::remove_file(&ply).unwrap();
+        assert_eq!(from_ply.tri_count(), 2);
+        assert_consistent(&from_ply);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::linalg::Vector3;
+
+#[derive(Clone, Copy, Debug)]
+pub enum FogMode {
+    //  Fog ramps from none at `start` to full at `end`.
+    Linear {
+        start: f32,
+        end: f32,
+    },
+    Exponential {
+        density: f32,
+    },
+    ExponentialSquared {
+        density: f32,
+    },
+    //  Exponential fog whose density is `density` at height `base` and
+    //  falls off as exp(-falloff * (y - base)) above it, integrated along
+    //  the view ray.
+    Height {
+        density: f32,
+        base: f32,
+        falloff: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum FogColor {
+    Color(Vector3),
+    //  Blurred environment radiance along the view ray.
+    Environment,
+    //  Whatever the background shows behind the pixel.
+    Background,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub color: FogColor,
+}
+
+impl Fog {
+    pub fn new(mode: FogMode, color: FogColor) -> Self {
+        Self { mode, color }
+    }
+
+    //  Fraction of the surface color at `pos` that reaches `eye`, in [0, 1].
+    pub fn visibility(&self, eye: Vector3, pos: Vector3) -> f32 {
+        let dist = (pos - eye).norm();
+        let f = match self.mode {
+            FogMode::Linear { start, end } => (end - dist) / (end - start).max(1e-6),
+            FogMode::Exponential { density } => (-density * dist).exp(),
+            FogMode::ExponentialSquared { density } => (-(density * dist).powi(2)).exp(),
+            FogMode::Height {
+                density,
+                base,
+                falloff,
+            } => {
+                let dy = pos.v[1] - eye.v[1];
+                let k = falloff * dy;
+                //  (1 - e^-k) / k, which tends to 1 for level rays.
+                let shape = if k.abs() < 1e-4 {
+                    1. - k / 2.
+                } else {
+                    (1. - (-k).exp()) / k
+                };
+                let depth = density * (-falloff * (eye.v[1] - base)).exp() * dist * shape;
+                (-depth).exp()
+            }
+        };
+        f.clamp(0., 1.)
+    }
+}
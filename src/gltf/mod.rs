@@ -0,0 +1,814 @@
+mod json;
+
+use crate::{
+    animation::{Animation, Channel, Interpolation, Keyframe, Track},
+    linalg::{transform::Transform, Matrix4, Quaternion, Vector2, Vector3},
+    material::Material,
+    model::Model,
+    node::{Node, NodeId},
+    scene::Scene,
+    texture::Texture,
+};
+use json::Json;
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+//  What `import` added to the scene. `nodes` maps glTF node indices to scene
+//  nodes, None for nodes outside the imported scene; primitives of
+//  multi-primitive meshes hang below their node as children of their own.
+pub struct Gltf {
+    pub nodes: Vec<Option<NodeId>>,
+    pub roots: Vec<NodeId>,
+    pub animations: Vec<Animation>,
+}
+
+//  Loads a glTF 2.0 file, JSON with external or embedded buffers or binary
+//  GLB, into `scene`: meshes, the node hierarchy of the default scene,
+//  metallic-roughness materials with their textures, and node animations.
+//  Skins and morph targets are not imported.
+pub fn import<P>(scene: &mut Scene, path: P) -> Result<Gltf, Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+    let (doc, bin) = if bytes.starts_with(b"glTF") {
+        parse_glb(&bytes)?
+    } else {
+        (Json::parse(std::str::from_utf8(&bytes)?)?, None)
+    };
+    let version = doc.get("asset").get("version").as_str().unwrap_or("");
+    if !version.starts_with('2') {
+        return Err(format!("gltf: unsupported version '{}'", version).into());
+    }
+    let dir = path.parent().map_or(PathBuf::new(), Path::to_path_buf);
+    let mut buffers = vec![];
+    for b in doc.get("buffers").items() {
+        buffers.push(match b.get("uri").as_str() {
+            Some(uri) => load_uri(&dir, uri)?,
+            None => bin
+                .clone()
+                .ok_or("gltf: buffer without uri outside of a GLB")?,
+        });
+    }
+    let mut loader = Loader {
+        doc: &doc,
+        dir,
+        buffers,
+        images: vec![None; doc.get("images").items().len()],
+    };
+
+    let materials = (0..doc.get("materials").items().len())
+        .map(|i| loader.material(i))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut meshes = vec![];
+    for mesh in doc.get("meshes").items() {
+        let mut prims = vec![];
+        for prim in mesh.get("primitives").items() {
+            let mut model = loader.primitive(prim)?;
+            model.set_material(match prim.get("material").as_usize() {
+                Some(m) => materials.get(m).ok_or("gltf: bad material index")?.clone(),
+                None => Material::pbr(Vector3::new(1., 1., 1.), 1., 1.),
+            });
+            prims.push(Rc::new(model));
+        }
+        meshes.push(prims);
+    }
+
+    //  Nodes of the default scene, or every node that is nobody's child.
+    let gnodes = doc.get("nodes").items();
+    let roots = match doc
+        .get("scenes")
+        .at(doc.get("scene").as_usize().unwrap_or(0))
+        .get("nodes")
+    {
+        Json::Array(a) => a.iter().filter_map(Json::as_usize).collect(),
+        _ => {
+            let mut is_child = vec![false; gnodes.len()];
+            for n in gnodes {
+                for c in n.get("children").items().iter().filter_map(Json::as_usize) {
+                    if c < is_child.len() {
+                        is_child[c] = true;
+                    }
+                }
+            }
+            (0..gnodes.len())
+                .filter(|&i| !is_child[i])
+                .collect::<Vec<_>>()
+        }
+    };
+    let mut ret = Gltf {
+        nodes: vec![None; gnodes.len()],
+        roots: vec![],
+        animations: vec![],
+    };
+    let mut stack = roots.iter().rev().map(|&i| (i, None)).collect::<Vec<_>>();
+    while let Some((i, parent)) = stack.pop() {
+        let n = gnodes.get(i).ok_or("gltf: bad node index")?;
+        if ret.nodes[i].is_some() {
+            return Err("gltf: node graph is not a forest".into());
+        }
+        let mut node = Node::new().transform(node_transform(n));
+        let prims = match n.get("mesh").as_usize() {
+            Some(m) => meshes.get(m).ok_or("gltf: bad mesh index")?.as_slice(),
+            None => &[],
+        };
+        if let [mesh] = prims {
+            node.mesh = Some(mesh.clone());
+        }
+        let id = scene.add_node(node, parent);
+        if prims.len() > 1 {
+            for mesh in prims {
+                scene.add_node(Node::with_mesh(mesh.clone()), Some(id));
+            }
+        }
+        ret.nodes[i] = Some(id);
+        if parent.is_none() {
+            ret.roots.push(id);
+        }
+        for c in n.get("children").items().iter().rev() {
+            stack.push((c.as_usize().ok_or("gltf: bad child index")?, Some(id)));
+        }
+    }
+
+    for anim in doc.get("animations").items() {
+        ret.animations.push(loader.animation(anim, &ret.nodes)?);
+    }
+    Ok(ret)
+}
+
+//  The JSON chunk and the optional binary chunk.
+type Glb = (Json, Option<Vec<u8>>);
+
+fn parse_glb(bytes: &[u8]) -> Result<Glb, Box<dyn Error>> {
+    let u32_at = |i: usize| -> Result<u32, Box<dyn Error>> {
+        let b = bytes.get(i..i + 4).ok_or("glb: truncated")?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    if u32_at(4)? != 2 {
+        return Err("glb: unsupported container version".into());
+    }
+    let len = (u32_at(8)? as usize).min(bytes.len());
+    let (mut doc, mut bin) = (None, None);
+    let mut i = 12;
+    while i + 8 <= len {
+        let (clen, ctype) = (u32_at(i)? as usize, u32_at(i + 4)?);
+        let data = bytes
+            .get(i + 8..i + 8 + clen)
+            .ok_or("glb: truncated chunk")?;
+        match ctype {
+            0x4e4f534a => doc = Some(Json::parse(std::str::from_utf8(data)?)?),
+            0x004e4942 => bin = Some(data.to_vec()),
+            _ => {}
+        }
+        i += 8 + clen;
+    }
+    Ok((doc.ok_or("glb: no JSON chunk")?, bin))
+}
+
+fn load_uri(dir: &Path, uri: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, payload) = data
+            .split_once(";base64,")
+            .ok_or("gltf: data uri is not base64")?;
+        return base64_decode(payload);
+    }
+    Ok(fs::read(dir.join(percent_decode(uri)))?)
+}
+
+fn percent_decode(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut ret = vec![];
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%' && i + 2 < b.len() {
+            if let (Some(hi), Some(lo)) = (hex_digit(b[i + 1]), hex_digit(b[i + 2])) {
+                ret.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+        ret.push(b[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&ret).into_owned()
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+fn base64_decode(s: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut ret = Vec::with_capacity(s.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in s.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            _ => return Err("gltf: invalid base64".into()),
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            ret.push((acc >> bits) as u8);
+        }
+    }
+    Ok(ret)
+}
+
+fn node_transform(n: &Json) -> Transform {
+    let m = n.get("matrix").floats();
+    if m.len() == 16 {
+        //  Column-major.
+        let mut mat = Matrix4::identity();
+        for (i, v) in m.iter().enumerate() {
+            mat.v[i % 4][i / 4] = *v;
+        }
+        return Transform::from_mat(mat);
+    }
+    let (t, r, s) = (
+        n.get("translation").floats(),
+        n.get("rotation").floats(),
+        n.get("scale").floats(),
+    );
+    Transform::from_trs(
+        vec3(&t).unwrap_or(Vector3::new(0., 0., 0.)),
+        quat(&r).unwrap_or(Quaternion::new(1., 0., 0., 0.)),
+        vec3(&s).unwrap_or(Vector3::new(1., 1., 1.)),
+    )
+}
+
+fn vec3(v: &[f32]) -> Option<Vector3> {
+    (v.len() >= 3).then(|| Vector3::new(v[0], v[1], v[2]))
+}
+
+//  glTF stores quaternions as x, y, z, w.
+fn quat(v: &[f32]) -> Option<Quaternion> {
+    (v.len() >= 4).then(|| Quaternion::new(v[3], v[0], v[1], v[2]))
+}
+
+struct Loader<'a> {
+    doc: &'a Json,
+    dir: PathBuf,
+    buffers: Vec<Vec<u8>>,
+    images: Vec<Option<Rc<Texture>>>,
+}
+
+impl Loader<'_> {
+    //  Bytes of a buffer view and its stride, 0 when tightly packed.
+    fn view(&self, i: usize) -> Result<(&[u8], usize), Box<dyn Error>> {
+        let v = self.doc.get("bufferViews").at(i);
+        let buf = self
+            .buffers
+            .get(v.get("buffer").as_usize().ok_or("gltf: bad buffer view")?)
+            .ok_or("gltf: bad buffer index")?;
+        let off = v.get("byteOffset").as_usize().unwrap_or(0);
+        let len = v
+            .get("byteLength")
+            .as_usize()
+            .ok_or("gltf: bad buffer view")?;
+        let data = off
+            .checked_add(len)
+            .and_then(|end| buf.get(off..end))
+            .ok_or("gltf: buffer view out of range")?;
+        Ok((data, v.get("byteStride").as_usize().unwrap_or(0)))
+    }
+
+    //  Accessor elements flattened to `f64`, wide enough for any index,
+    //  with normalized integers mapped to [0, 1] or [-1, 1]. Returns the
+    //  values and the number of components per element.
+    fn accessor(&self, i: usize) -> Result<(Vec<f64>, usize), Box<dyn Error>> {
+        let a = self.doc.get("accessors").at(i);
+        if a.is_null() {
+            return Err(format!("gltf: bad accessor index {}", i).into());
+        }
+        let ncomp = match a.get("type").as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err("gltf: bad accessor type".into()),
+        };
+        let ctype = a.get("componentType").as_usize().unwrap_or(0);
+        let normalized = a.get("normalized") == &Json::Bool(true);
+        let count = a
+            .get("count")
+            .as_usize()
+            .ok_or("gltf: accessor without count")?;
+        let size = component_size(ctype)?;
+        //  Check the count against the data before allocating for it.
+        let view = match a.get("bufferView").as_usize() {
+            Some(v) => {
+                let (data, stride) = self.view(v)?;
+                let off = a.get("byteOffset").as_usize().unwrap_or(0);
+                if !fits(data.len(), off, count, stride, size * ncomp) {
+                    return Err("gltf: accessor out of range".into());
+                }
+                Some((data, stride, off))
+            }
+            None if count > MAX_ZERO_ACCESSOR => return Err("gltf: accessor too large".into()),
+            None => None,
+        };
+        let mut ret = vec![0f64; count * ncomp];
+        if let Some((data, stride, off)) = view {
+            read_components(
+                data.get(off..).ok_or("gltf: accessor out of range")?,
+                stride,
+                ctype,
+                normalized,
+                ncomp,
+                &mut ret,
+            )?;
+        }
+        let sparse = a.get("sparse");
+        if !sparse.is_null() {
+            let n = sparse.get("count").as_usize().unwrap_or(0);
+            let (ind, val) = (sparse.get("indices"), sparse.get("values"));
+            let (idata, _) =
+                self.view(ind.get("bufferView").as_usize().ok_or("gltf: bad sparse")?)?;
+            let ioff = ind.get("byteOffset").as_usize().unwrap_or(0);
+            let ict = ind.get("componentType").as_usize().unwrap_or(0);
+            let (vdata, _) =
+                self.view(val.get("bufferView").as_usize().ok_or("gltf: bad sparse")?)?;
+            let voff = val.get("byteOffset").as_usize().unwrap_or(0);
+            let isize = component_size(ict)?;
+            if n > count
+                || !fits(idata.len(), ioff, n, isize, isize)
+                || !fits(vdata.len(), voff, n, size * ncomp, size * ncomp)
+            {
+                return Err("gltf: sparse accessor out of range".into());
+            }
+            let mut idx = vec![0f64; n];
+            read_components(&idata[ioff..], 0, ict, false, 1, &mut idx)?;
+            let mut vals = vec![0f64; n * ncomp];
+            read_components(&vdata[voff..], 0, ctype, normalized, ncomp, &mut vals)?;
+            for (k, &j) in idx.iter().enumerate() {
+                let j = j as usize;
+                if j < count {
+                    ret[j * ncomp..(j + 1) * ncomp]
+                        .copy_from_slice(&vals[k * ncomp..(k + 1) * ncomp]);
+                }
+            }
+        }
+        Ok((ret, ncomp))
+    }
+
+    fn floats(&self, i: usize) -> Result<(Vec<f32>, usize), Box<dyn Error>> {
+        let (v, n) = self.accessor(i)?;
+        Ok((v.into_iter().map(|x| x as f32).collect(), n))
+    }
+
+    fn texture(&mut self, info: &Json) -> Result<Option<Rc<Texture>>, Box<dyn Error>> {
+        let Some(t) = info.get("index").as_usize() else {
+            return Ok(None);
+        };
+        let img = self
+            .doc
+            .get("textures")
+            .at(t)
+            .get("source")
+            .as_usize()
+            .ok_or("gltf: texture without source")?;
+        if let Some(tex) = self.images.get(img).ok_or("gltf: bad image index")? {
+            return Ok(Some(tex.clone()));
+        }
+        let desc = self.doc.get("images").at(img);
+        let bytes = match (desc.get("uri").as_str(), desc.get("bufferView").as_usize()) {
+            (Some(uri), _) => load_uri(&self.dir, uri)?,
+            (None, Some(v)) => self.view(v)?.0.to_vec(),
+            _ => return Err("gltf: image without data".into()),
+        };
+        let tex = Rc::new(Texture::new(image::load_from_memory(&bytes)?.to_rgb8()));
+        self.images[img] = Some(tex.clone());
+        Ok(Some(tex))
+    }
+
+    //  Normal maps are not used by the renderer and are skipped.
+    fn material(&mut self, i: usize) -> Result<Material, Box<dyn Error>> {
+        let m = self.doc.get("materials").at(i);
+        let pbr = m.get("pbrMetallicRoughness");
+        let base = pbr.get("baseColorFactor").floats();
+        let mut ret = Material::pbr(
+            vec3(&base).unwrap_or(Vector3::new(1., 1., 1.)),
+            pbr.get("metallicFactor").as_f32().unwrap_or(1.),
+            pbr.get("roughnessFactor").as_f32().unwrap_or(1.),
+        );
+        ret.texture = self.texture(pbr.get("baseColorTexture"))?;
+        ret.metallic_roughness_map = self.texture(pbr.get("metallicRoughnessTexture"))?;
+        ret.occlusion_map = self.texture(m.get("occlusionTexture"))?;
+        ret.emissive_map = self.texture(m.get("emissiveTexture"))?;
+        ret.emissive = vec3(&m.get("emissiveFactor").floats()).unwrap_or(Vector3::new(0., 0., 0.));
+        if let Some(s) = m
+            .get("extensions")
+            .get("KHR_materials_emissive_strength")
+            .get("emissiveStrength")
+            .as_f32()
+        {
+            ret.emissive_strength = s;
+        }
+        Ok(ret)
+    }
+
+    fn primitive(&self, prim: &Json) -> Result<Model, Box<dyn Error>> {
+        let attr = prim.get("attributes");
+        let (pos, _) = self.floats(
+            attr.get("POSITION")
+                .as_usize()
+                .ok_or("gltf: primitive without positions")?,
+        )?;
+        let norm = match attr.get("NORMAL").as_usize() {
+            Some(a) => Some(self.floats(a)?.0),
+            None => None,
+        };
+        let uv = match attr.get("TEXCOORD_0").as_usize() {
+            Some(a) => Some(self.floats(a)?.0),
+            None => None,
+        };
+        //  RGB or RGBA, alpha is dropped.
+        let color = match attr.get("COLOR_0").as_usize() {
+            Some(a) => Some(self.floats(a)?),
+            None => None,
+        };
+        let n = pos.len() / 3;
+        let mut ret = Model::new();
+        for i in 0..n {
+            ret.add_vertex(
+                Vector3::new(pos[i * 3], pos[i * 3 + 1], pos[i * 3 + 2]),
+                //  glTF puts the texture origin at the top left.
+                uv.as_ref()
+                    .and_then(|t| t.get(i * 2..i * 2 + 2))
+                    .map(|t| Vector2::new(t[0], 1. - t[1])),
+                norm.as_ref()
+                    .and_then(|v| v.get(i * 3..i * 3 + 3))
+                    .map(|v| Vector3::new(v[0], v[1], v[2])),
+            );
+            if let Some((c, k)) = &color {
+                if let Some(c) = c.get(i * k..i * k + 3) {
+                    ret.set_color(i, Vector3::new(c[0], c[1], c[2]));
+                }
+            }
+        }
+        let idx = match prim.get("indices").as_usize() {
+            Some(a) => self
+                .accessor(a)?
+                .0
+                .into_iter()
+                .map(|x| x as usize)
+                .collect(),
+            None => (0..n).collect::<Vec<usize>>(),
+        };
+        if idx.iter().any(|&i| i >= n) {
+            return Err("gltf: index out of range".into());
+        }
+        match prim.get("mode").as_usize().unwrap_or(4) {
+            4 => idx
+                .chunks_exact(3)
+                .for_each(|t| ret.add_tri(t[0], t[1], t[2])),
+            5 => {
+                for (k, t) in idx.windows(3).enumerate() {
+                    if k % 2 == 0 {
+                        ret.add_tri(t[0], t[1], t[2]);
+                    } else {
+                        ret.add_tri(t[1], t[0], t[2]);
+                    }
+                }
+            }
+            6 => {
+                for t in idx.windows(2).skip(1) {
+                    ret.add_tri(idx[0], t[0], t[1]);
+                }
+            }
+            //  Points and lines are not rendered.
+            _ => {}
+        }
+        if norm.is_none() {
+            ret.set_flat_normals();
+        }
+        Ok(ret)
+    }
+
+    fn animation(
+        &self,
+        anim: &Json,
+        nodes: &[Option<NodeId>],
+    ) -> Result<Animation, Box<dyn Error>> {
+        let mut ret = Animation::new();
+        let samplers = anim.get("samplers");
+        for ch in anim.get("channels").items() {
+            let target = ch.get("target");
+            //  Nodes outside of the imported scene are skipped.
+            let Some(&Some(node)) = target.get("node").as_usize().and_then(|n| nodes.get(n)) else {
+                continue;
+            };
+            let s = samplers.at(ch.get("sampler").as_usize().ok_or("gltf: bad sampler")?);
+            let interp = match s.get("interpolation").as_str().unwrap_or("LINEAR") {
+                "STEP" => Interpolation::Step,
+                "CUBICSPLINE" => Interpolation::CubicSpline,
+                _ => Interpolation::Linear,
+            };
+            let (times, _) = self.floats(s.get("input").as_usize().ok_or("gltf: bad sampler")?)?;
+            let (vals, _) = self.floats(s.get("output").as_usize().ok_or("gltf: bad sampler")?)?;
+            match target.get("path").as_str() {
+                Some("translation") => {
+                    let track = keyframes(&times, &vals, 3, interp, |v| vec3(v).unwrap());
+                    ret.add_channel(Channel::Translation(node, track));
+                }
+                Some("rotation") => {
+                    let track = keyframes(&times, &vals, 4, interp, |v| quat(v).unwrap());
+                    ret.add_channel(Channel::Rotation(node, track));
+                }
+                Some("scale") => {
+                    let track = keyframes(&times, &vals, 3, interp, |v| vec3(v).unwrap());
+                    ret.add_channel(Channel::Scale(node, track));
+                }
+                //  Morph target weights.
+                _ => {}
+            }
+        }
+        Ok(ret)
+    }
+}
+
+//  Cubic-spline outputs hold an in-tangent, value and out-tangent per key.
+fn keyframes<T, F>(times: &[f32], vals: &[f32], n: usize, interp: Interpolation, f: F) -> Track<T>
+where
+    T: crate::animation::Animatable,
+    F: Fn(&[f32]) -> T,
+{
+    let mut track = Track::new(interp);
+    let per_key = if interp == Interpolation::CubicSpline {
+        3
+    } else {
+        1
+    };
+    for (k, &time) in times.iter().enumerate() {
+        let Some(v) = vals.get(k * per_key * n..(k + 1) * per_key * n) else {
+            break;
+        };
+        track.insert(if per_key == 3 {
+            Keyframe {
+                time,
+                value: f(&v[n..2 * n]),
+                tangents: Some((f(&v[..n]), f(&v[2 * n..]))),
+            }
+        } else {
+            Keyframe {
+                time,
+                value: f(v),
+                tangents: None,
+            }
+        });
+    }
+    track
+}
+
+//  Accessors without a buffer view are zeros, and nothing in the file
+//  bounds their size.
+const MAX_ZERO_ACCESSOR: usize = 1 << 24;
+
+fn component_size(ctype: usize) -> Result<usize, Box<dyn Error>> {
+    match ctype {
+        5120 | 5121 => Ok(1),
+        5122 | 5123 => Ok(2),
+        5125 | 5126 => Ok(4),
+        _ => Err(format!("gltf: bad component type {}", ctype).into()),
+    }
+}
+
+//  Whether `count` elements of `elem` bytes, `stride` apart (0 when tightly
+//  packed) from `off`, fit in `len` bytes.
+fn fits(len: usize, off: usize, count: usize, stride: usize, elem: usize) -> bool {
+    let stride = if stride == 0 { elem } else { stride };
+    count == 0
+        || (count - 1)
+            .checked_mul(stride)
+            .and_then(|n| n.checked_add(elem)?.checked_add(off))
+            .is_some_and(|end| end <= len)
+}
+
+fn read_components(
+    data: &[u8],
+    stride: usize,
+    ctype: usize,
+    normalized: bool,
+    ncomp: usize,
+    out: &mut [f64],
+) -> Result<(), Box<dyn Error>> {
+    let size = component_size(ctype)?;
+    let stride = if stride == 0 { size * ncomp } else { stride };
+    for (e, vals) in out.chunks_exact_mut(ncomp).enumerate() {
+        for (c, val) in vals.iter_mut().enumerate() {
+            let b = e
+                .checked_mul(stride)
+                .and_then(|o| o.checked_add(c * size))
+                .and_then(|o| data.get(o..)?.get(..size))
+                .ok_or("gltf: accessor out of range")?;
+            *val = match ctype {
+                5120 => {
+                    let x = b[0] as i8 as f64;
+                    if normalized {
+                        (x / 127.).max(-1.)
+                    } else {
+                        x
+                    }
+                }
+                5121 => b[0] as f64 / if normalized { 255. } else { 1. },
+                5122 => {
+                    let x = i16::from_le_bytes([b[0], b[1]]) as f64;
+                    if normalized {
+                        (x / 32767.).max(-1.)
+                    } else {
+                        x
+                    }
+                }
+                5123 => {
+                    u16::from_le_bytes([b[0], b[1]]) as f64 / if normalized { 65535. } else { 1. }
+                }
+                5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            };
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_multibyte() {
+        assert_eq!(percent_decode("a%20b.bin"), "a b.bin");
+        assert_eq!(percent_decode("a%é.bin"), "a%é.bin");
+        assert_eq!(percent_decode("%C3%A9%"), "é%");
+    }
+
+    //  One triangle whose second vertex is moved by a sparse accessor, a
+    //  textureless material, two nested nodes and one outside the scene.
+    fn binary() -> Vec<u8> {
+        let mut ret = vec![];
+        for x in [0f32, 0., 0., 1., 0., 0., 0., 1., 0.] {
+            ret.extend(x.to_le_bytes());
+        }
+        for i in [0u16, 1, 2, 0, 1, 0] {
+            ret.extend(i.to_le_bytes());
+        }
+        for x in [2f32, 0., 0.] {
+            ret.extend(x.to_le_bytes());
+        }
+        ret
+    }
+
+    fn document(buffer: &str, count: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "buffers": [{}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+                    {{"buffer": 0, "byteOffset": 36, "byteLength": 6}},
+                    {{"buffer": 0, "byteOffset": 44, "byteLength": 2}},
+                    {{"buffer": 0, "byteOffset": 48, "byteLength": 12}}
+                ],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": {}, "type": "VEC3",
+                      "sparse": {{"count": 1,
+                                 "indices": {{"bufferView": 2, "componentType": 5123}},
+                                 "values": {{"bufferView": 3}}}}}},
+                    {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+                ],
+                "materials": [{{
+                    "pbrMetallicRoughness": {{"baseColorFactor": [0.5, 0.25, 1, 1],
+                                             "metallicFactor": 0.3, "roughnessFactor": 0.7}},
+                    "emissiveFactor": [1, 0, 0]
+                }}],
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}},
+                                             "indices": 1, "material": 0}}]}}],
+                "nodes": [
+                    {{"children": [1], "translation": [1, 2, 3],
+                      "rotation": [0, 0, 0.70710678, 0.70710678], "scale": [2, 2, 2]}},
+                    {{"mesh": 0, "matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 5, 6, 7, 1]}},
+                    {{"mesh": 0}}
+                ],
+                "scenes": [{{"nodes": [0]}}]
+            }}"#,
+            buffer, count
+        )
+    }
+
+    fn base64(bytes: &[u8]) -> String {
+        let table = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut ret = String::new();
+        for c in bytes.chunks(3) {
+            let n = c
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+            for i in 0..4 {
+                ret.push(if i <= c.len() {
+                    table[(n >> (18 - 6 * i) & 63) as usize] as char
+                } else {
+                    '='
+                });
+            }
+        }
+        ret
+    }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+        let mut ret = b"glTF".to_vec();
+        ret.extend(2u32.to_le_bytes());
+        ret.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        for (chunk, ty) in [(&json, 0x4e4f534au32), (&bin, 0x004e4942)] {
+            ret.extend((chunk.len() as u32).to_le_bytes());
+            ret.extend(ty.to_le_bytes());
+            ret.extend(chunk);
+        }
+        ret
+    }
+
+    fn import_bytes(bytes: &[u8], name: &str) -> Result<(Scene, Gltf), Box<dyn Error>> {
+        let path = std::env::temp_dir().join(format!("tinyrenderer_rs_{}", name));
+        fs::write(&path, bytes)?;
+        let mut scene = Scene::new();
+        let ret = import(&mut scene, &path);
+        fs::remove_file(&path)?;
+        Ok((scene, ret?))
+    }
+
+    fn check(scene: &Scene, gltf: &Gltf) {
+        assert_eq!(gltf.roots.len(), 1);
+        assert_eq!(gltf.nodes.len(), 3);
+        assert!(gltf.nodes[2].is_none());
+        let nodes = gltf.nodes[..2]
+            .iter()
+            .map(|n| n.unwrap())
+            .collect::<Vec<_>>();
+        let close = |a: Vector3, b: Vector3| (a - b).norm() < 1e-5;
+
+        let root = scene.node(nodes[0]).transform.mat();
+        let p = (root * Vector3::new(1., 0., 0.).homo_point()).vec3_homo();
+        assert!(close(p, Vector3::new(1., 4., 3.)));
+        let child = scene.node(nodes[1]).transform.mat();
+        let p = (child * Vector3::new(0., 0., 0.).homo_point()).vec3_homo();
+        assert!(close(p, Vector3::new(5., 6., 7.)));
+
+        let model = scene.node(nodes[1]).mesh.clone().unwrap();
+        assert_eq!(model.tri_count(), 1);
+        let pos = model.vertices().iter().map(|v| v.pos).collect::<Vec<_>>();
+        assert_eq!(pos.len(), 3);
+        for (p, q) in pos.iter().zip([(0., 0., 0.), (2., 0., 0.), (0., 1., 0.)]) {
+            assert!(close(*p, Vector3::new(q.0, q.1, q.2)));
+        }
+
+        let mtl = model.material();
+        assert!(close(mtl.base_color, Vector3::new(0.5, 0.25, 1.)));
+        assert!(close(mtl.emissive, Vector3::new(1., 0., 0.)));
+        assert!((mtl.metallic - 0.3).abs() < 1e-6 && (mtl.roughness - 0.7).abs() < 1e-6);
+    }
+
+    #[test]
+    fn import_embedded_gltf() {
+        let buffer = format!(
+            r#"{{"byteLength": 60, "uri": "data:application/octet-stream;base64,{}"}}"#,
+            base64(&binary())
+        );
+        let doc = document(&buffer, "3");
+        let (scene, gltf) = import_bytes(doc.as_bytes(), "embedded.gltf").unwrap();
+        check(&scene, &gltf);
+    }
+
+    #[test]
+    fn import_glb() {
+        let doc = document(r#"{"byteLength": 60}"#, "3");
+        let (scene, gltf) = import_bytes(&glb(&doc, &binary()), "binary.glb").unwrap();
+        check(&scene, &gltf);
+    }
+
+    #[test]
+    fn rejects_counts_beyond_the_data() {
+        for count in ["4", "1e14"] {
+            let doc = document(r#"{"byteLength": 60}"#, count);
+            assert!(import_bytes(&glb(&doc, &binary()), "count.glb").is_err());
+        }
+        let doc =
+            document(r#"{"byteLength": 60}"#, "3").replace(r#""count": 1,"#, r#""count": 9,"#);
+        assert!(import_bytes(&glb(&doc, &binary()), "sparse.glb").is_err());
+    }
+}
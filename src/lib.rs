@@ -0,0 +1,24 @@
+pub mod animation;
+pub mod background;
+pub mod bounds;
+pub mod bvh;
+pub mod camera;
+pub mod encode;
+pub mod environment;
+pub mod export;
+pub mod fog;
+pub mod gltf;
+pub mod light;
+pub mod linalg;
+pub mod material;
+pub mod model;
+pub mod node;
+pub mod outline;
+pub mod ply;
+pub mod scene;
+pub mod shading;
+pub mod ssao;
+pub mod stl;
+pub mod texture;
+pub mod triangle;
+pub mod utils;
@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
 
-use super::vector::Vector;
+use super::vector::{Vector, Vector3};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Matrix<const R: usize, const C: usize> {
@@ -49,7 +49,7 @@ impl<const R: usize, const C: usize> SubAssign for Matrix<R, C> {
     fn sub_assign(&mut self, other: Self) {
         for i in 0..R {
             for j in 0..C {
-                self.v[i][j] += other.v[i][j];
+                self.v[i][j] -= other.v[i][j];
             }
         }
     }
@@ -125,3 +125,231 @@ impl<const R: usize, const C: usize> Matrix<R, C> {
         res
     }
 }
+
+impl<const R: usize, const C: usize> Matrix<R, C> {
+    pub fn transpose(&self) -> Matrix<C, R> {
+        let mut res = Matrix::<C, R> { v: [[0f32; R]; C] };
+        for i in 0..R {
+            for j in 0..C {
+                res.v[j][i] = self.v[i][j];
+            }
+        }
+        res
+    }
+}
+
+impl<const N: usize> Matrix<N, N> {
+    //  Gaussian elimination with partial pivoting.
+    pub fn determinant(&self) -> f32 {
+        let mut m = self.v;
+        let mut det = 1f32;
+        for c in 0..N {
+            let p = (c..N)
+                .max_by(|&a, &b| m[a][c].abs().total_cmp(&m[b][c].abs()))
+                .unwrap();
+            if m[p][c] == 0. {
+                return 0.;
+            }
+            if p != c {
+                m.swap(p, c);
+                det = -det;
+            }
+            det *= m[c][c];
+            let pivot = m[c];
+            for row in m.iter_mut().skip(c + 1) {
+                let f = row[c] / pivot[c];
+                for (x, p) in row.iter_mut().zip(pivot).skip(c) {
+                    *x -= f * p;
+                }
+            }
+        }
+        det
+    }
+
+    //  Gauss-Jordan elimination, `None` for singular matrices. Pivots are
+    //  judged relative to the largest entry, so uniformly small matrices
+    //  still invert.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut m = self.v;
+        let mut inv = Self::identity().v;
+        let scale = m.iter().flatten().fold(0f32, |s, x| s.max(x.abs()));
+        for c in 0..N {
+            let p = (c..N)
+                .max_by(|&a, &b| m[a][c].abs().total_cmp(&m[b][c].abs()))
+                .unwrap();
+            if m[p][c].abs() <= scale * N as f32 * f32::EPSILON {
+                return None;
+            }
+            m.swap(p, c);
+            inv.swap(p, c);
+            let d = 1. / m[c][c];
+            for k in 0..N {
+                m[c][k] *= d;
+                inv[c][k] *= d;
+            }
+            for r in 0..N {
+                if r == c {
+                    continue;
+                }
+                let f = m[r][c];
+                for k in 0..N {
+                    m[r][k] -= f * m[c][k];
+                    inv[r][k] -= f * inv[c][k];
+                }
+            }
+        }
+        Some(Self { v: inv })
+    }
+}
+
+impl Matrix3 {
+    pub fn from_mat4(m: &Matrix4) -> Self {
+        let mut res = Self::zeros();
+        for i in 0..3 {
+            for j in 0..3 {
+                res.v[i][j] = m.v[i][j];
+            }
+        }
+        res
+    }
+}
+
+impl Matrix4 {
+    pub fn from_mat3(m: &Matrix3) -> Self {
+        let mut res = Self::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                res.v[i][j] = m.v[i][j];
+            }
+        }
+        res
+    }
+
+    //  Adjugate of the linear part, the transposed cofactor matrix, and
+    //  its determinant.
+    fn adjugate3(&self) -> (Matrix3, f32) {
+        let a = Matrix3::from_mat4(self).v;
+        let mut adj = Matrix3::zeros();
+        for i in 0..3 {
+            for j in 0..3 {
+                let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+                let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+                adj.v[i][j] = a[r0][c0] * a[r1][c1] - a[r0][c1] * a[r1][c0];
+            }
+        }
+        let det = a[0][0] * adj.v[0][0] + a[0][1] * adj.v[1][0] + a[0][2] * adj.v[2][0];
+        (adj, det)
+    }
+
+    //  Inverse of an affine transform [A t; 0 1], i.e. [A^-1 -A^-1 t; 0 1],
+    //  with A^-1 from the cofactors of the linear part. A is singular when
+    //  its determinant is negligible next to the product of its column
+    //  lengths, which bounds it, so the test does not depend on the scale.
+    pub fn inverse_affine(&self) -> Option<Self> {
+        let (mut ai, det) = self.adjugate3();
+        let bound = (0..3)
+            .map(|j| Vector3::new(self.v[0][j], self.v[1][j], self.v[2][j]).norm())
+            .product::<f32>();
+        if det.abs() <= bound * f32::EPSILON {
+            return None;
+        }
+        ai *= 1. / det;
+        let t = Vector3::new(self.v[0][3], self.v[1][3], self.v[2][3]);
+        let ti = ai * t;
+        let mut res = Self::from_mat3(&ai);
+        for i in 0..3 {
+            res.v[i][3] = -ti.v[i];
+        }
+        Some(res)
+    }
+
+    //  Matrix for transforming normals, the inverse-transpose of the linear
+    //  part, so normals stay perpendicular to surfaces under non-uniform scale.
+    //  It is built from the cofactors, so it is only right up to scale and
+    //  normals must be renormalized, but it needs no division by the
+    //  determinant and survives tiny and degenerate scales.
+    pub fn normal_matrix(&self) -> Matrix3 {
+        let (adj, det) = self.adjugate3();
+        let m = adj.transpose();
+        let scale = m.v.iter().flatten().fold(0f32, |s, x| s.max(x.abs()));
+        if scale == 0. {
+            return Matrix3::identity();
+        }
+        //  Mirroring flips the cofactors against the inverse.
+        m * (if det < 0. { -1. } else { 1. } / scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linalg::transform::Transform;
+
+    fn trs(scale: f32) -> Matrix4 {
+        Transform::new()
+            .scale(Vector3::new(scale, 2. * scale, scale))
+            .rotation(Vector3::new(1., 2., 3.).normalize(), 0.7)
+            .translation(Vector3::new(1., -2., 3.))
+            .mat()
+    }
+
+    fn assert_near(a: Matrix4, b: Matrix4, eps: f32) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!(
+                    (a.v[i][j] - b.v[i][j]).abs() <= eps,
+                    "{:?} != {:?}",
+                    a.v,
+                    b.v
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_affine_round_trips() {
+        for scale in [1., 0.001, 250.] {
+            let m = trs(scale);
+            let inv = m.inverse_affine().expect("invertible");
+            assert_near(m * inv, Matrix4::identity(), 1e-3);
+            assert_near(inv * m, Matrix4::identity(), 1e-3);
+            let g = m.inverse().expect("invertible");
+            assert_near(g * m, Matrix4::identity(), 1e-3);
+        }
+    }
+
+    #[test]
+    fn inverse_affine_rejects_singular() {
+        let flat = Transform::new().scale(Vector3::new(1., 0., 1.)).mat();
+        assert!(flat.inverse_affine().is_none());
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn normal_matrix_keeps_rotation_at_small_scale() {
+        let rot = Transform::new()
+            .rotation(Vector3::new(0., 1., 0.), 0.5)
+            .mat();
+        let n = Vector3::new(1., 0., 0.);
+        let expect = (Matrix3::from_mat4(&rot) * n).normalize();
+        for scale in [1., 0.001, 0.00001] {
+            let m = Transform::new()
+                .scale(Vector3::new(scale, scale, scale))
+                .rotation(Vector3::new(0., 1., 0.), 0.5)
+                .mat();
+            let got = (m.normal_matrix() * n).normalize();
+            assert!((got - expect).norm() < 1e-5, "{:?} at {}", got, scale);
+        }
+    }
+
+    #[test]
+    fn normal_matrix_stays_perpendicular() {
+        let m = trs(1.);
+        let (a, b) = (Vector3::new(1., 0., 0.), Vector3::new(0., 1., 0.));
+        let lin = Matrix3::from_mat4(&m);
+        let n = m.normal_matrix() * a.cross(b);
+        assert!(n.dot(lin * a).abs() < 1e-5);
+        assert!(n.dot(lin * b).abs() < 1e-5);
+        assert!(n.dot((lin * a).cross(lin * b)) > 0.);
+    }
+}
@@ -1,6 +1,8 @@
 pub mod matrix;
+pub mod quaternion;
 pub mod transform;
 pub mod vector;
 
 pub use matrix::*;
+pub use quaternion::*;
 pub use vector::*;
@@ -0,0 +1,269 @@
+use std::ops::{Add, Mul, Neg};
+
+use super::{Matrix4, Vector3};
+use crate::utils::EPS;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+//  Axis order of Euler angles. The angles are rotations about the fixed
+//  world axes applied in the named order, e.g. `XYZ` rotates about x first
+//  and z last, so the resulting rotation is Rz * Ry * Rx.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EulerOrder {
+    XYZ,
+    XZY,
+    YXZ,
+    YZX,
+    ZXY,
+    ZYX,
+}
+
+impl EulerOrder {
+    fn axes(&self) -> [usize; 3] {
+        match self {
+            EulerOrder::XYZ => [0, 1, 2],
+            EulerOrder::XZY => [0, 2, 1],
+            EulerOrder::YXZ => [1, 0, 2],
+            EulerOrder::YZX => [1, 2, 0],
+            EulerOrder::ZXY => [2, 0, 1],
+            EulerOrder::ZYX => [2, 1, 0],
+        }
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+impl Add for Quaternion {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(
+            self.w + other.w,
+            self.x + other.x,
+            self.y + other.y,
+            self.z + other.z,
+        )
+    }
+}
+
+impl Mul<f32> for Quaternion {
+    type Output = Self;
+
+    fn mul(self, other: f32) -> Self {
+        Self::new(
+            self.w * other,
+            self.x * other,
+            self.y * other,
+            self.z * other,
+        )
+    }
+}
+
+//  Hamilton product, `a * b` rotates by `b` first and then by `a`.
+impl Mul for Quaternion {
+    type Output = Self;
+
+    fn mul(self, o: Self) -> Self {
+        Self::new(
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        )
+    }
+}
+
+impl Mul<Vector3> for Quaternion {
+    type Output = Vector3;
+
+    fn mul(self, v: Vector3) -> Vector3 {
+        let q = self * Quaternion::new(0., v.v[0], v.v[1], v.v[2]) * self.conjugate();
+        Vector3::new(q.x, q.y, q.z)
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Quaternion {
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1., 0., 0., 0.)
+    }
+
+    pub fn from_axis_angle(axis: Vector3, ang: f32) -> Self {
+        let axis = axis.normalize();
+        let (s, c) = (ang / 2.).sin_cos();
+        Self::new(c, axis.v[0] * s, axis.v[1] * s, axis.v[2] * s)
+    }
+
+    //  Returns a unit axis and an angle in [0, 2pi], the axis is arbitrary
+    //  for the identity rotation.
+    pub fn to_axis_angle(&self) -> (Vector3, f32) {
+        let q = self.normalize();
+        let s = (1. - q.w * q.w).max(0.).sqrt();
+        let ang = 2. * q.w.clamp(-1., 1.).acos();
+        if s < EPS {
+            (Vector3::new(1., 0., 0.), ang)
+        } else {
+            (Vector3::new(q.x / s, q.y / s, q.z / s), ang)
+        }
+    }
+
+    pub fn from_euler(order: EulerOrder, angles: Vector3) -> Self {
+        let mut q = Self::identity();
+        for (k, &axis) in order.axes().iter().enumerate() {
+            let mut v = Vector3::new(0., 0., 0.);
+            v.v[axis] = 1.;
+            q = Self::from_axis_angle(v, angles.v[k]) * q;
+        }
+        q
+    }
+
+    //  Inverse of `from_euler`, the middle angle is kept in [-pi/2, pi/2]
+    //  and the last one is set to zero at gimbal lock.
+    pub fn to_euler(&self, order: EulerOrder) -> Vector3 {
+        let m = self.to_mat4().v;
+        let [i, j, k] = order.axes();
+        let s = if (j + 3 - i) % 3 == 1 { 1. } else { -1. };
+        let sb = -s * m[k][i];
+        if sb.abs() < 1. - EPS {
+            Vector3::new(
+                (s * m[k][j]).atan2(m[k][k]),
+                sb.asin(),
+                (s * m[j][i]).atan2(m[i][i]),
+            )
+        } else {
+            Vector3::new((-s * m[j][k]).atan2(m[j][j]), sb.clamp(-1., 1.).asin(), 0.)
+        }
+    }
+
+    pub fn from_mat4(m: &Matrix4) -> Self {
+        let m = m.v;
+        let tr = m[0][0] + m[1][1] + m[2][2];
+        let q = if tr > 0. {
+            let s = (tr + 1.).sqrt() * 2.;
+            Self::new(
+                s / 4.,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1. + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.;
+            Self::new(
+                (m[2][1] - m[1][2]) / s,
+                s / 4.,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = (1. + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.;
+            Self::new(
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                s / 4.,
+                (m[1][2] + m[2][1]) / s,
+            )
+        } else {
+            let s = (1. + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.;
+            Self::new(
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.,
+            )
+        };
+        q.normalize()
+    }
+
+    pub fn to_mat4(&self) -> Matrix4 {
+        let Self { w, x, y, z } = self.normalize();
+        Matrix4 {
+            v: [
+                [
+                    1. - 2. * (y * y + z * z),
+                    2. * (x * y - w * z),
+                    2. * (x * z + w * y),
+                    0.,
+                ],
+                [
+                    2. * (x * y + w * z),
+                    1. - 2. * (x * x + z * z),
+                    2. * (y * z - w * x),
+                    0.,
+                ],
+                [
+                    2. * (x * z - w * y),
+                    2. * (y * z + w * x),
+                    1. - 2. * (x * x + y * y),
+                    0.,
+                ],
+                [0., 0., 0., 1.],
+            ],
+        }
+    }
+
+    pub fn dot(&self, other: Self) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn norm(&self) -> f32 {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        let norm = self.norm();
+        assert!(norm > EPS);
+        *self * (1. / norm)
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn inverse(&self) -> Self {
+        self.conjugate() * (1. / self.dot(*self))
+    }
+
+    //  Normalized linear interpolation along the shorter arc, cheaper than
+    //  slerp but not constant in angular velocity.
+    pub fn nlerp(&self, other: Self, t: f32) -> Self {
+        let other = if self.dot(other) < 0. { -other } else { other };
+        (*self * (1. - t) + other * t).normalize()
+    }
+
+    pub fn slerp(&self, other: Self, t: f32) -> Self {
+        let mut cos = self.dot(other);
+        let other = if cos < 0. {
+            cos = -cos;
+            -other
+        } else {
+            other
+        };
+        if cos > 1. - EPS {
+            return self.nlerp(other, t);
+        }
+        let ang = cos.acos();
+        let sin = ang.sin();
+        (*self * (((1. - t) * ang).sin() / sin) + other * ((t * ang).sin() / sin)).normalize()
+    }
+}
@@ -1,9 +1,16 @@
-use super::{Matrix4, Vector3};
+use super::{Matrix3, Matrix4, Quaternion, Vector3};
 
+#[derive(Clone, Copy, Debug)]
 pub struct Transform {
     mat: Matrix4,
 }
 
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Transform {
     pub fn new() -> Self {
         Self {
@@ -11,6 +18,38 @@ impl Transform {
         }
     }
 
+    pub fn from_mat(mat: Matrix4) -> Self {
+        Self { mat }
+    }
+
+    //  Scales, then rotates, then translates.
+    pub fn from_trs(translation: Vector3, rotation: Quaternion, scale: Vector3) -> Self {
+        Self::new()
+            .scale(scale)
+            .rotate_quat(rotation)
+            .translation(translation)
+    }
+
+    //  Splits an affine transform without shear back into translation,
+    //  rotation and scale, a reflection is folded into a negative x scale.
+    pub fn decompose(&self) -> (Vector3, Quaternion, Vector3) {
+        let m = self.mat.v;
+        let translation = Vector3::new(m[0][3], m[1][3], m[2][3]);
+        let lin = Matrix3::from_mat4(&self.mat);
+        let flip = if lin.determinant() < 0. { -1. } else { 1. };
+        let mut cols = lin.transpose().v.map(|c| Vector3 { v: c });
+        cols[0] *= flip;
+        let mut scale = Vector3 {
+            v: cols.map(|c| c.norm()),
+        };
+        scale.v[0] *= flip;
+        let cols = cols.map(|c| c.normalize_or_zero());
+        let rot = Matrix4::from_mat3(&Matrix3 {
+            v: [0, 1, 2].map(|i| cols.map(|c| c.v[i])),
+        });
+        (translation, Quaternion::from_mat4(&rot), scale)
+    }
+
     pub fn translation(mut self, pos: Vector3) -> Self {
         let m = Matrix4 {
             v: [
@@ -43,13 +82,18 @@ impl Transform {
                 [0., -axis.v[2], axis.v[1], 0.],
                 [axis.v[2], 0., -axis.v[0], 0.],
                 [-axis.v[1], axis.v[0], 0., 0.],
-                [0., 0., 0., 1.],
+                [0., 0., 0., 0.],
             ],
         };
         self.mat = self.mat + k * ang.sin() * self.mat + k * k * (1. - ang.cos()) * self.mat;
         self
     }
 
+    pub fn rotate_quat(mut self, q: Quaternion) -> Self {
+        self.mat = q.to_mat4() * self.mat;
+        self
+    }
+
     pub fn mat(&self) -> Matrix4 {
         self.mat
     }
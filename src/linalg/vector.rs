@@ -101,7 +101,7 @@ impl<const T: usize> Div for Vector<T> {
     fn div(self, other: Self) -> Self {
         let mut res = Vector::<T> { v: [0f32; T] };
         for i in 0..T {
-            res.v[i] = self.v[i] * other.v[i];
+            res.v[i] = self.v[i] / other.v[i];
         }
         res
     }
@@ -135,6 +135,15 @@ impl<const T: usize> Vector<T> {
         assert!(norm > EPS);
         *self / norm
     }
+    //  Missing normals are stored as zero vectors and must stay that way.
+    pub fn normalize_or_zero(&self) -> Self {
+        let norm = self.norm();
+        if norm > EPS {
+            *self / norm
+        } else {
+            *self
+        }
+    }
 }
 
 impl Vector2 {
@@ -169,6 +178,17 @@ impl Vector3 {
     pub fn homo_vec(&self) -> Vector4 {
         Vector4::new(self.v[0], self.v[1], self.v[2], 0.)
     }
+
+    //  Two unit vectors completing this unit vector to an orthonormal basis.
+    pub fn basis(&self) -> (Self, Self) {
+        let a = if self.v[0].abs() > 0.9 {
+            Vector3::new(0., 1., 0.)
+        } else {
+            Vector3::new(1., 0., 0.)
+        };
+        let t = self.cross(a).normalize();
+        (t, self.cross(t))
+    }
 }
 
 impl Vector4 {
@@ -192,7 +212,7 @@ impl Vector4 {
 macro_rules! vect {
     ($($v:expr),+) => {
         {
-            use crate::linalg::Vector;
+            use $crate::linalg::Vector;
             Vector{ v: [$($v,)+] }
         }
     }
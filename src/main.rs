@@ -1,19 +1,14 @@
-mod camera;
-mod light;
-mod linalg;
-mod model;
-mod scene;
-mod texture;
-mod triangle;
-mod utils;
-
-use camera::Camera;
 use image::RgbImage;
-use light::Light;
-use linalg::transform::Transform;
-use model::Model;
-use scene::Scene;
-use std::{error::Error, f32::consts::PI};
+use std::{error::Error, f32::consts::PI, rc::Rc};
+use tinyrenderer_rs::{
+    camera::Camera,
+    light::{Attenuation, Light},
+    linalg::transform::Transform,
+    model::Model,
+    node::Node,
+    scene::Scene,
+    vect,
+};
 
 const WIDTH: usize = 1024;
 const HEIGHT: usize = 1024;
@@ -29,16 +24,16 @@ fn main() -> Result<(), Box<dyn Error>> {
     );
     scene.set_camera(camera);
     let mut model = Model::open("test/spot_triangulated_good.obj")?;
-    let mat = Transform::new()
-        .translation(vect![0.5, 0., 0.5])
-        .rotation(vect![0., 1., 0.], PI / 4.)
-        .mat();
-    model.apply(mat);
     model.load_texture("test/spot_texture.png")?;
-    scene.add_model(model);
+    let model = Rc::new(model);
+    let trans = Transform::new()
+        .translation(vect![0.5, 0., 0.5])
+        .rotation(vect![0., 1., 0.], PI / 4.);
+    scene.add_node(Node::with_mesh(model).transform(trans), None);
     let light1 = Light::Point {
         pos: vect![0., 3., 0.],
         li: vect![10., 10., 10.],
+        att: Attenuation::InverseSquare,
     };
     let light2 = Light::Parallel {
         dir: vect![1., 0., 0.],
@@ -0,0 +1,194 @@
+use crate::{
+    linalg::{Vector2, Vector3},
+    texture::Texture,
+};
+use std::rc::Rc;
+
+pub const BP_P: f32 = 160.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ShadingModel {
+    #[default]
+    BlinnPhong,
+    //  Cook-Torrance GGX metallic-roughness, as in glTF 2.0.
+    Pbr,
+    //  Cel shading, diffuse lighting quantized into `bands` or looked up
+    //  in `ramp` by n.l along u, with a hard specular highlight.
+    Toon,
+}
+
+//  How lighting is evaluated across a triangle: once with the face normal,
+//  at the vertices and interpolated, or per fragment with interpolated
+//  normals.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Shading {
+    Flat,
+    Gouraud,
+    #[default]
+    Phong,
+}
+
+//  `texture` is the base color map and is multiplied by `base_color`. The
+//  metallic-roughness map follows glTF, with roughness in green and
+//  metalness in blue, and the occlusion map stores AO in red.
+//
+//  PBR materials are lit in linear space as in glTF: their base color and
+//  emissive maps are decoded from sRGB and the result is encoded again for
+//  display. Other models use every map as stored.
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub model: ShadingModel,
+    pub shading: Shading,
+    pub texture: Option<Rc<Texture>>,
+    pub base_color: Vector3,
+    pub specular: Vector3,
+    pub shininess: f32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub ao: f32,
+    pub emissive: Vector3,
+    pub emissive_strength: f32,
+    pub metallic_roughness_map: Option<Rc<Texture>>,
+    pub occlusion_map: Option<Rc<Texture>>,
+    pub emissive_map: Option<Rc<Texture>>,
+    pub bands: usize,
+    pub ramp: Option<Rc<Texture>>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Material {
+    pub fn new() -> Self {
+        Self {
+            model: ShadingModel::BlinnPhong,
+            shading: Shading::Phong,
+            texture: None,
+            base_color: Vector3::new(1., 1., 1.),
+            specular: Vector3::new(1., 1., 1.),
+            shininess: BP_P,
+            metallic: 0.,
+            roughness: 1.,
+            ao: 1.,
+            emissive: Vector3::new(0., 0., 0.),
+            emissive_strength: 1.,
+            metallic_roughness_map: None,
+            occlusion_map: None,
+            emissive_map: None,
+            bands: 3,
+            ramp: None,
+        }
+    }
+
+    pub fn pbr(base_color: Vector3, metallic: f32, roughness: f32) -> Self {
+        Self {
+            model: ShadingModel::Pbr,
+            base_color,
+            metallic,
+            roughness,
+            ..Self::new()
+        }
+    }
+
+    pub fn toon(base_color: Vector3, bands: usize) -> Self {
+        Self {
+            model: ShadingModel::Toon,
+            base_color,
+            bands,
+            ..Self::new()
+        }
+    }
+
+    pub fn ramp(mut self, ramp: Rc<Texture>) -> Self {
+        self.ramp = Some(ramp);
+        self
+    }
+
+    pub fn albedo_at(&self, uv: Vector2) -> Vector3 {
+        self.base_color * self.texel(uv)
+    }
+
+    //  Emitted radiance, `emissive` times the emissive map scaled by
+    //  `emissive_strength`, which may exceed 1 for HDR output.
+    pub fn emissive_at(&self, uv: Vector2) -> Vector3 {
+        let e = self.emissive * self.emissive_strength;
+        match &self.emissive_map {
+            Some(texture) => e * self.color_sample(texture, uv),
+            None => e,
+        }
+    }
+
+    //  Base color map sample, white without a map.
+    pub fn texel(&self, uv: Vector2) -> Vector3 {
+        match &self.texture {
+            Some(texture) => self.color_sample(texture, uv),
+            None => Vector3::new(1., 1., 1.),
+        }
+    }
+
+    //  Whether the material is shaded in linear space, see above.
+    pub fn is_linear(&self) -> bool {
+        self.model == ShadingModel::Pbr
+    }
+
+    fn color_sample(&self, texture: &Texture, uv: Vector2) -> Vector3 {
+        if self.is_linear() {
+            texture.sample_srgb(uv.v[0], uv.v[1])
+        } else {
+            texture.sample(uv.v[0], uv.v[1])
+        }
+    }
+
+    pub fn texture(mut self, texture: Rc<Texture>) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    pub fn shading(mut self, shading: Shading) -> Self {
+        self.shading = shading;
+        self
+    }
+
+    pub fn specular(mut self, specular: Vector3, shininess: f32) -> Self {
+        self.specular = specular;
+        self.shininess = shininess;
+        self
+    }
+
+    pub fn emissive(mut self, emissive: Vector3) -> Self {
+        self.emissive = emissive;
+        self
+    }
+
+    pub fn emissive_map(mut self, map: Rc<Texture>, strength: f32) -> Self {
+        self.emissive_map = Some(map);
+        self.emissive_strength = strength;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::linear_to_srgb;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn pbr_color_maps_are_decoded() {
+        let grey = Rc::new(Texture::new(RgbImage::from_pixel(2, 2, Rgb([128; 3]))));
+        let uv = Vector2::new(0.5, 0.5);
+        let phong = Material::new().texture(grey.clone());
+        let pbr = Material::pbr(Vector3::new(1., 1., 1.), 0., 1.)
+            .texture(grey.clone())
+            .emissive(Vector3::new(1., 1., 1.))
+            .emissive_map(grey, 1.);
+        assert!((phong.albedo_at(uv).v[0] - 128. / 255.).abs() < 1e-6);
+        for c in [pbr.albedo_at(uv), pbr.emissive_at(uv)] {
+            assert!((c.v[0] - 0.2158).abs() < 1e-3);
+            assert!((linear_to_srgb(c.v[0]) - 128. / 255.).abs() < 1e-5);
+        }
+    }
+}
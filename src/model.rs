@@ -1,10 +1,16 @@
 use crate::{
+    bounds::{Aabb, Sphere},
+    bvh::{Bvh, Hit},
+    export,
     linalg::{Matrix4, Vector2, Vector3},
+    material::Material,
+    ply, stl,
     texture::Texture,
     triangle::Triangle,
-    vect,
 };
 use std::{
+    cell::OnceCell,
+    collections::HashMap,
     error::Error,
     fs::File,
     io::{BufRead, BufReader},
@@ -12,34 +18,97 @@ use std::{
     rc::Rc,
 };
 
-type TriInd = (usize, usize, usize);
+//  One entry of the vertex buffer. Each unique combination of attributes
+//  is stored once and shared by every triangle using it.
+#[derive(Clone, Copy, Debug)]
+pub struct Vertex {
+    pub pos: Vector3,
+    pub uv: Vector2,
+    pub norm: Vector3,
+    pub color: Vector3,
+}
+
+impl Vertex {
+    pub fn new(pos: Vector3) -> Self {
+        Self {
+            pos,
+            uv: Vector2::new(0., 0.),
+            norm: Vector3::new(0., 0., 0.),
+            color: Vector3::new(1., 1., 1.),
+        }
+    }
+
+    //  Bit pattern of all attributes, for exact deduplication.
+    fn key(&self) -> [u32; 11] {
+        let mut ret = [0u32; 11];
+        let attrs = self
+            .pos
+            .v
+            .iter()
+            .chain(&self.uv.v)
+            .chain(&self.norm.v)
+            .chain(&self.color.v);
+        for (k, x) in ret.iter_mut().zip(attrs) {
+            *k = x.to_bits();
+        }
+        ret
+    }
+}
 
+//  Indexed triangle mesh: a vertex buffer and triangles of indices into it,
+//  counter-clockwise.
 pub struct Model {
-    vertices: Vec<Vector3>,
-    tex_coords: Vec<Vector2>,
-    norms: Vec<Vector3>,
-    tris: Vec<(TriInd, TriInd, TriInd)>,
-    texture: Option<Rc<Texture>>,
+    pub(crate) vertices: Vec<Vertex>,
+    pub(crate) indices: Vec<[u32; 3]>,
+    pub(crate) has_uv: bool,
+    pub(crate) has_norm: bool,
+    pub(crate) has_color: bool,
+    material: Material,
+    //  Computed on first use, reset when the geometry changes.
+    bounds: OnceCell<(Aabb, Sphere)>,
+    bvh: OnceCell<Bvh>,
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Model {
     pub fn new() -> Self {
         Self {
             vertices: Vec::new(),
-            tex_coords: Vec::new(),
-            norms: Vec::new(),
-            tris: Vec::new(),
-            texture: None,
+            indices: Vec::new(),
+            has_uv: false,
+            has_norm: false,
+            has_color: false,
+            material: Material::new(),
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
         }
     }
 
+    //  Reads OBJ, or PLY and STL by extension.
     pub fn open<P>(path: P) -> Result<Self, Box<dyn Error>>
     where
         P: AsRef<Path>,
     {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("ply") => return ply::load(path),
+            Some("stl") => return stl::load(path),
+            _ => {}
+        }
         let f = File::open(path)?;
         let reader = BufReader::new(f);
-        let mut ret = Self::new();
+        let (mut vertices, mut tex_coords, mut norms) = (vec![], vec![], vec![]);
+        let mut colors = vec![];
+        let mut tris = vec![];
         for line in reader.lines() {
             let tl = line?;
             let strs = tl.split_whitespace().collect::<Vec<&str>>();
@@ -50,10 +119,17 @@ impl Model {
                 "v" => {
                     let (x, y, z): (f32, f32, f32) =
                         (strs[1].parse()?, strs[2].parse()?, strs[3].parse()?);
-                    if let Some(w) = strs.get(4) {
-                        ret.vertices.push(Vector3::new(x, y, z) / w.parse::<f32>()?);
+                    //  Either a w coordinate or, as a common extension, an
+                    //  RGB color.
+                    if strs.len() >= 7 {
+                        vertices.push(Vector3::new(x, y, z));
+                        let (r, g, b) = (strs[4].parse()?, strs[5].parse()?, strs[6].parse()?);
+                        colors.resize(vertices.len() - 1, Vector3::new(1., 1., 1.));
+                        colors.push(Vector3::new(r, g, b));
+                    } else if let Some(w) = strs.get(4) {
+                        vertices.push(Vector3::new(x, y, z) / w.parse::<f32>()?);
                     } else {
-                        ret.vertices.push(Vector3::new(x, y, z));
+                        vertices.push(Vector3::new(x, y, z));
                     }
                 }
                 "vt" => {
@@ -65,91 +141,456 @@ impl Model {
                             0.
                         },
                     );
-                    ret.tex_coords.push(Vector2::new(u, v));
+                    tex_coords.push(Vector2::new(u, v));
                 }
                 "vn" => {
                     let (x, y, z): (f32, f32, f32) =
                         (strs[1].parse()?, strs[2].parse()?, strs[3].parse()?);
-                    ret.norms.push(Vector3::new(x, y, z));
+                    norms.push(Vector3::new(x, y, z));
                 }
                 "f" => {
-                    let mut tris = [(0usize, 0usize, 0usize); 3];
-                    for i in 0..3 {
+                    let mut tri = [(0usize, usize::MAX, usize::MAX); 3];
+                    for (i, c) in tri.iter_mut().enumerate() {
                         let inds = strs[i + 1].split('/').collect::<Vec<&str>>();
-                        tris[i].0 = inds[0].parse::<usize>()? - 1;
-                        tris[i].1 = inds[1].parse::<usize>().map_or(usize::MAX, |u| u - 1);
-                        tris[i].2 = inds[2].parse::<usize>().map_or(usize::MAX, |u| u - 1);
+                        let at = |k: usize| {
+                            inds.get(k)
+                                .and_then(|s| s.parse::<usize>().ok())
+                                .map_or(usize::MAX, |u| u - 1)
+                        };
+                        *c = (inds[0].parse::<usize>()? - 1, at(1), at(2));
                     }
-                    ret.tris.push((tris[0], tris[1], tris[2]));
+                    tris.push(tri);
                 }
                 _ => {}
             }
         }
+
+        //  OBJ indexes each attribute separately, every distinct
+        //  position/uv/normal triple becomes one vertex.
+        let mut ret = Self::new();
+        ret.has_color = !colors.is_empty();
+        let mut seen = HashMap::new();
+        for tri in tris {
+            let mut idx = [0u32; 3];
+            for (k, c) in tri.into_iter().enumerate() {
+                if let Some(&i) = seen.get(&c) {
+                    idx[k] = i;
+                    continue;
+                }
+                let (v, t, n) = c;
+                let mut vert = Vertex::new(*vertices.get(v).ok_or("obj: index out of range")?);
+                if let Some(&uv) = tex_coords.get(t) {
+                    vert.uv = uv;
+                    ret.has_uv = true;
+                }
+                if let Some(&norm) = norms.get(n) {
+                    vert.norm = norm;
+                    ret.has_norm = true;
+                }
+                if let Some(&color) = colors.get(v) {
+                    vert.color = color;
+                }
+                ret.vertices.push(vert);
+                idx[k] = ret.vertices.len() as u32 - 1;
+                seen.insert(c, idx[k]);
+            }
+            ret.indices.push(idx);
+        }
         Ok(ret)
     }
 
+    //  Appends a vertex and returns its index for `add_tri`.
+    pub fn add_vertex(
+        &mut self,
+        pos: Vector3,
+        uv: Option<Vector2>,
+        norm: Option<Vector3>,
+    ) -> usize {
+        let mut v = Vertex::new(pos);
+        if let Some(uv) = uv {
+            v.uv = uv;
+            self.has_uv = true;
+        }
+        if let Some(norm) = norm {
+            v.norm = norm;
+            self.has_norm = true;
+        }
+        self.vertices.push(v);
+        self.invalidate();
+        self.vertices.len() - 1
+    }
+
+    pub fn add_tri(&mut self, a: usize, b: usize, c: usize) {
+        self.indices.push([a as u32, b as u32, c as u32]);
+        self.invalidate();
+    }
+
+    pub fn set_color(&mut self, i: usize, color: Vector3) {
+        self.vertices[i].color = color;
+        self.has_color = true;
+    }
+
+    pub fn has_colors(&self) -> bool {
+        self.has_color
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[[u32; 3]] {
+        &self.indices
+    }
+
+    fn invalidate(&mut self) {
+        self.bounds.take();
+        self.bvh.take();
+    }
+
+    fn bounds(&self) -> &(Aabb, Sphere) {
+        self.bounds.get_or_init(|| {
+            let points = self.vertices.iter().map(|v| v.pos);
+            (
+                Aabb::from_points(points.clone()),
+                Sphere::from_points(points),
+            )
+        })
+    }
+
+    //  Bounds in model space, of all vertices.
+    pub fn aabb(&self) -> Aabb {
+        self.bounds().0
+    }
+
+    pub fn bounding_sphere(&self) -> Sphere {
+        self.bounds().1
+    }
+
+    //  Built on first use.
+    pub fn bvh(&self) -> &Bvh {
+        self.bvh.get_or_init(|| Bvh::build(self))
+    }
+
+    //  Closest triangle hit by the ray in model space, see `Bvh::intersect`.
+    pub fn raycast(&self, origin: Vector3, dir: Vector3, t_max: f32) -> Option<Hit> {
+        self.bvh().intersect(self, origin, dir, t_max)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn tri_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn corners(&self) -> Vec<[Vertex; 3]> {
+        self.indices
+            .iter()
+            .map(|t| t.map(|i| self.vertices[i as usize]))
+            .collect()
+    }
+
+    //  Rebuilds the buffers from one vertex per triangle corner, sharing
+    //  corners whose attributes are identical.
+    fn reindex(&mut self, corners: Vec<[Vertex; 3]>) {
+        let mut seen = HashMap::new();
+        self.vertices.clear();
+        self.indices.clear();
+        self.invalidate();
+        for tri in corners {
+            let idx = tri.map(|v| {
+                *seen.entry(v.key()).or_insert_with(|| {
+                    self.vertices.push(v);
+                    self.vertices.len() as u32 - 1
+                })
+            });
+            self.indices.push(idx);
+        }
+    }
+
+    //  Replaces every normal by the geometric normal of its face, taken
+    //  counter-clockwise.
+    pub fn set_flat_normals(&mut self) {
+        let mut corners = self.corners();
+        for tri in &mut corners {
+            let n = (tri[1].pos - tri[0].pos)
+                .cross(tri[2].pos - tri[0].pos)
+                .normalize_or_zero();
+            for v in tri.iter_mut() {
+                v.norm = n;
+            }
+        }
+        self.has_norm = true;
+        self.reindex(corners);
+    }
+
+    //  Merges vertex positions closer than `eps`, keeping the first, and
+    //  drops triangles that collapse. Vertices only become shared where
+    //  their other attributes agree as well.
+    pub fn weld(&mut self, eps: f32) {
+        let key = |p: Vector3| p.v.map(|x| (x / eps).round() as i64);
+        //  Every representative in each cell, as several can share one.
+        let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        let mut positions: Vec<Vector3> = vec![];
+        for v in &self.vertices {
+            let (p, k) = (v.pos, key(v.pos));
+            //  Look at the neighbouring cells too, so that points straddling
+            //  a cell boundary still merge, and keep the earliest match.
+            let mut found: Option<usize> = None;
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let Some(cell) = cells.get(&[k[0] + dx, k[1] + dy, k[2] + dz]) else {
+                            continue;
+                        };
+                        for &j in cell {
+                            if (positions[j] - p).norm() <= eps && found.is_none_or(|f| j < f) {
+                                found = Some(j);
+                            }
+                        }
+                    }
+                }
+            }
+            remap.push(found.unwrap_or_else(|| {
+                positions.push(p);
+                cells.entry(k).or_default().push(positions.len() - 1);
+                positions.len() - 1
+            }));
+        }
+        let corners = self
+            .indices
+            .iter()
+            .filter_map(|t| {
+                let [a, b, c] = t.map(|i| remap[i as usize]);
+                (a != b && b != c && c != a).then(|| {
+                    t.map(|i| Vertex {
+                        pos: positions[remap[i as usize]],
+                        ..self.vertices[i as usize]
+                    })
+                })
+            })
+            .collect();
+        self.reindex(corners);
+    }
+
+    //  Recomputes normals from the faces, averaging over the faces around a
+    //  position whose normals are within `crease_angle` radians of each
+    //  other, weighted by their corner angle. Small angles give faceted
+    //  results and pi gives fully smooth ones. Faces only share a position
+    //  once it is welded.
+    pub fn compute_normals(&mut self, crease_angle: f32) {
+        let mut corners = self.corners();
+        let mut ids = HashMap::new();
+        let pos_id = corners
+            .iter()
+            .map(|t| {
+                t.map(|v| {
+                    let n = ids.len();
+                    *ids.entry(v.pos.v.map(f32::to_bits)).or_insert(n)
+                })
+            })
+            .collect::<Vec<[usize; 3]>>();
+        let face_n = corners
+            .iter()
+            .map(|t| {
+                (t[1].pos - t[0].pos)
+                    .cross(t[2].pos - t[0].pos)
+                    .normalize_or_zero()
+            })
+            .collect::<Vec<Vector3>>();
+        let mut around = vec![vec![]; ids.len()];
+        for (f, t) in corners.iter().enumerate() {
+            for k in 0..3 {
+                let [p, q, r] = [k, (k + 1) % 3, (k + 2) % 3].map(|i| t[i].pos);
+                let ang = (q - p)
+                    .normalize_or_zero()
+                    .dot((r - p).normalize_or_zero())
+                    .clamp(-1., 1.)
+                    .acos();
+                around[pos_id[f][k]].push((f, ang));
+            }
+        }
+        let cos_crease = crease_angle.cos();
+        for (f, tri) in corners.iter_mut().enumerate() {
+            for (k, v) in tri.iter_mut().enumerate() {
+                let mut n = Vector3::new(0., 0., 0.);
+                for &(g, ang) in &around[pos_id[f][k]] {
+                    if face_n[g].dot(face_n[f]) >= cos_crease - 1e-6 {
+                        n += face_n[g] * ang;
+                    }
+                }
+                v.norm = n.normalize_or_zero();
+            }
+        }
+        //  Corners of the same smoothing group end up with bit-identical
+        //  normals and so share a vertex again.
+        self.has_norm = true;
+        self.reindex(corners);
+    }
+
+    //  Reorders triangles so that consecutive ones reuse recently
+    //  transformed vertices, after Forsyth's "Linear-Speed Vertex Cache
+    //  Optimisation", then renumbers vertices in order of first use.
+    pub fn optimize_vertex_cache(&mut self) {
+        const CACHE_SIZE: usize = 32;
+        let score = |pos: Option<usize>, remaining: usize| -> f32 {
+            if remaining == 0 {
+                return -1.;
+            }
+            //  The last triangle's vertices get a fixed score so that
+            //  strips are not favoured over fans.
+            let cache = match pos {
+                None => 0.,
+                Some(p) if p < 3 => 0.75,
+                Some(p) => (1. - (p - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5),
+            };
+            //  Boost vertices with few triangles left to finish them off.
+            cache + 2. * (remaining as f32).powf(-0.5)
+        };
+
+        let (nv, nt) = (self.vertices.len(), self.indices.len());
+        let mut tris_of = vec![vec![]; nv];
+        for (t, idx) in self.indices.iter().enumerate() {
+            for &i in idx {
+                tris_of[i as usize].push(t);
+            }
+        }
+        let mut cache_pos = vec![None; nv];
+        let mut vscore = tris_of
+            .iter()
+            .map(|t| score(None, t.len()))
+            .collect::<Vec<f32>>();
+        let tri_score =
+            |idx: &[u32; 3], vscore: &[f32]| idx.iter().map(|&i| vscore[i as usize]).sum::<f32>();
+        let mut tscore = self
+            .indices
+            .iter()
+            .map(|idx| tri_score(idx, &vscore))
+            .collect::<Vec<f32>>();
+        let mut emitted = vec![false; nt];
+        let mut order = Vec::with_capacity(nt);
+        let mut cache: Vec<usize> = vec![];
+        let mut next_unemitted = 0;
+        let mut best = (0..nt).max_by(|&a, &b| tscore[a].total_cmp(&tscore[b]));
+        while let Some(t) = best {
+            emitted[t] = true;
+            order.push(t);
+            let tri = self.indices[t].map(|i| i as usize);
+            for &v in &tri {
+                tris_of[v].retain(|&u| u != t);
+            }
+
+            let mut next = tri.to_vec();
+            next.extend(cache.iter().filter(|v| !tri.contains(v)));
+            let evicted = next.split_off(next.len().min(CACHE_SIZE));
+            for &v in &evicted {
+                cache_pos[v] = None;
+                vscore[v] = score(None, tris_of[v].len());
+            }
+            for (p, &v) in next.iter().enumerate() {
+                cache_pos[v] = Some(p);
+                vscore[v] = score(cache_pos[v], tris_of[v].len());
+            }
+
+            //  Only triangles touching the cache changed, the best of them
+            //  goes next.
+            best = None;
+            let mut best_score = f32::MIN;
+            for &v in next.iter().chain(&evicted) {
+                for &u in &tris_of[v] {
+                    tscore[u] = tri_score(&self.indices[u], &vscore);
+                    if tscore[u] > best_score {
+                        (best, best_score) = (Some(u), tscore[u]);
+                    }
+                }
+            }
+            cache = next;
+            if best.is_none() {
+                while next_unemitted < nt && emitted[next_unemitted] {
+                    next_unemitted += 1;
+                }
+                best = (next_unemitted < nt).then_some(next_unemitted);
+            }
+        }
+
+        let mut remap = vec![u32::MAX; nv];
+        let mut vertices = Vec::with_capacity(nv);
+        let indices = order
+            .iter()
+            .map(|&t| {
+                self.indices[t].map(|i| {
+                    if remap[i as usize] == u32::MAX {
+                        remap[i as usize] = vertices.len() as u32;
+                        vertices.push(self.vertices[i as usize]);
+                    }
+                    remap[i as usize]
+                })
+            })
+            .collect();
+        //  Keep unreferenced vertices, at the end.
+        for (i, v) in self.vertices.iter().enumerate() {
+            if remap[i] == u32::MAX {
+                vertices.push(*v);
+            }
+        }
+        self.vertices = vertices;
+        self.indices = indices;
+        self.invalidate();
+    }
+
     pub fn load_texture<P>(&mut self, path: P) -> Result<(), Box<dyn Error>>
     where
         P: AsRef<Path>,
     {
-        self.texture = Some(Rc::new(Texture::open(path)?));
+        self.material.texture = Some(Rc::new(Texture::open(path)?));
         Ok(())
     }
 
+    //  Writes the mesh and its material, see `export::save_obj`.
+    pub fn save_obj<P>(&self, path: P) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        export::save_obj(path, &[(self, Matrix4::identity(), &self.material)])
+    }
+
+    pub fn save_ply<P>(&self, path: P) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        export::save_ply(path, &[(self, Matrix4::identity(), &self.material)])
+    }
+
     pub fn apply(&mut self, mat: Matrix4) {
-        for p in &mut self.vertices {
-            *p = (mat * p.homo_point()).vec3_homo();
-        }
-        for v in &mut self.norms {
-            *v = (mat * v.homo_vec()).vec3_homo();
+        let nmat = mat.normal_matrix();
+        self.invalidate();
+        for v in &mut self.vertices {
+            v.pos = (mat * v.pos.homo_point()).vec3_homo();
+            v.norm = (nmat * v.norm).normalize_or_zero();
         }
     }
 
-    pub fn iter(&self) -> IterModel {
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    pub fn iter(&self) -> IterModel<'_> {
         IterModel { i: 0, model: self }
     }
 
     pub fn get_tri(&self, i: usize) -> Triangle {
-        let (v1, t1, n1) = self.tris[i].0;
-        let (v2, t2, n2) = self.tris[i].1;
-        let (v3, t3, n3) = self.tris[i].2;
+        let t = self.indices[i].map(|k| self.vertices[k as usize]);
         Triangle {
-            v: [self.vertices[v1], self.vertices[v2], self.vertices[v3]],
-            n: [
-                if n1 != usize::MAX {
-                    self.norms[n1]
-                } else {
-                    Vector3::new(0., 0., 0.)
-                },
-                if n2 != usize::MAX {
-                    self.norms[n2]
-                } else {
-                    Vector3::new(0., 0., 0.)
-                },
-                if n3 != usize::MAX {
-                    self.norms[n3]
-                } else {
-                    Vector3::new(0., 0., 0.)
-                },
-            ],
-            uv: [
-                if t1 != usize::MAX {
-                    self.tex_coords[t1]
-                } else {
-                    Vector2::new(0., 0.)
-                },
-                if t2 != usize::MAX {
-                    self.tex_coords[t2]
-                } else {
-                    Vector2::new(0., 0.)
-                },
-                if t3 != usize::MAX {
-                    self.tex_coords[t3]
-                } else {
-                    Vector2::new(0., 0.)
-                },
-            ],
-            texture: self.texture.clone(),
+            v: t.map(|v| v.pos),
+            n: t.map(|v| v.norm),
+            uv: t.map(|v| v.uv),
+            c: t.map(|v| v.color),
         }
     }
 }
@@ -159,11 +600,11 @@ pub struct IterModel<'a> {
     model: &'a Model,
 }
 
-impl<'a> Iterator for IterModel<'a> {
+impl Iterator for IterModel<'_> {
     type Item = Triangle;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.i >= self.model.tris.len() {
+        if self.i >= self.model.indices.len() {
             None
         } else {
             let ret = self.model.get_tri(self.i);
@@ -172,3 +613,100 @@ impl<'a> Iterator for IterModel<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hash_unit;
+
+    //  Average vertices transformed per triangle through a FIFO cache.
+    fn acmr(m: &Model, size: usize) -> f32 {
+        let mut cache = std::collections::VecDeque::new();
+        let mut misses = 0;
+        for &i in m.indices().iter().flatten() {
+            if !cache.contains(&i) {
+                misses += 1;
+                cache.push_back(i);
+                if cache.len() > size {
+                    cache.pop_front();
+                }
+            }
+        }
+        misses as f32 / m.tri_count() as f32
+    }
+
+    //  Triangles as corner positions, rotated to a canonical first corner
+    //  while keeping their winding, sorted.
+    fn triangle_set(m: &Model) -> Vec<[[u32; 3]; 3]> {
+        let mut ret = m
+            .indices()
+            .iter()
+            .map(|t| {
+                let c = t.map(|i| m.vertices()[i as usize].pos.v.map(f32::to_bits));
+                let k = (0..3).min_by_key(|&k| c[k]).unwrap();
+                [c[k], c[(k + 1) % 3], c[(k + 2) % 3]]
+            })
+            .collect::<Vec<_>>();
+        ret.sort();
+        ret
+    }
+
+    //  An n x n grid of quads, with its triangles scrambled or in rows.
+    fn grid(n: usize, scrambled: bool) -> Model {
+        let mut tris = vec![];
+        for y in 0..n {
+            for x in 0..n {
+                let i = y * (n + 1) + x;
+                for t in [[i, i + 1, i + n + 2], [i, i + n + 2, i + n + 1]] {
+                    tris.push((hash_unit(tris.len() as u32), t));
+                }
+            }
+        }
+        if scrambled {
+            tris.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        let mut m = Model::new();
+        for y in 0..=n {
+            for x in 0..=n {
+                m.add_vertex(Vector3::new(x as f32, y as f32, 0.), None, None);
+            }
+        }
+        for (_, t) in tris {
+            m.add_tri(t[0], t[1], t[2]);
+        }
+        m
+    }
+
+    #[test]
+    fn vertex_cache_order_keeps_triangles_and_cuts_misses() {
+        for scrambled in [true, false] {
+            let mut m = grid(30, scrambled);
+            let (before, set) = (acmr(&m, 16), triangle_set(&m));
+            m.optimize_vertex_cache();
+            let after = acmr(&m, 16);
+            assert_eq!(triangle_set(&m), set);
+            assert_eq!(m.vertex_count(), 31 * 31);
+            assert!(after <= before, "{} -> {}", before, after);
+            assert!(after < 0.8, "{} -> {}", before, after);
+        }
+    }
+
+    #[test]
+    fn weld_checks_every_vertex_in_a_cell() {
+        let mut m = Model::new();
+        //  The first two round to the same cell but are too far apart to
+        //  merge, the third only merges with the first.
+        let [a, b, c, d] = [
+            Vector3::new(0.4, 0.4, 0.4),
+            Vector3::new(-0.45, -0.45, -0.45),
+            Vector3::new(0.45, 0.45, 0.2),
+            Vector3::new(2., 0., 0.),
+        ]
+        .map(|p| m.add_vertex(p, None, None));
+        m.add_tri(a, b, d);
+        m.add_tri(c, b, d);
+        m.weld(1.);
+        assert_eq!(m.vertex_count(), 3);
+        assert_eq!(m.tri_count(), 2);
+    }
+}
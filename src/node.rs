@@ -0,0 +1,59 @@
+use crate::{linalg::transform::Transform, material::Material, model::Model};
+use std::rc::Rc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(pub(crate) usize);
+
+//  A node of the scene graph. The mesh is shared, so the same model can be
+//  instanced by any number of nodes, each with its own transform and material.
+#[derive(Clone)]
+pub struct Node {
+    pub transform: Transform,
+    pub mesh: Option<Rc<Model>>,
+    pub material: Option<Material>,
+    pub(crate) parent: Option<NodeId>,
+    pub(crate) children: Vec<NodeId>,
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Node {
+    pub fn new() -> Self {
+        Self {
+            transform: Transform::new(),
+            mesh: None,
+            material: None,
+            parent: None,
+            children: vec![],
+        }
+    }
+
+    pub fn with_mesh(mesh: Rc<Model>) -> Self {
+        Self {
+            mesh: Some(mesh),
+            ..Self::new()
+        }
+    }
+
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{camera::Camera, linalg::Vector3};
+
+//  Silhouette and crease lines found by edge detection on the depth and
+//  normal of the nearest surface per pixel. A pixel is an edge when a
+//  neighbour within `width` pixels is background, is farther by more than
+//  `depth_threshold` relative to its own view depth, or has a normal
+//  turned by more than `crease_angle` radians. Only the nearer side of a
+//  discontinuity is marked, so lines sit on the object.
+#[derive(Clone, Copy, Debug)]
+pub struct Outline {
+    pub color: Vector3,
+    pub width: usize,
+    pub depth_threshold: f32,
+    pub crease_angle: f32,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Self {
+            color: Vector3::new(0., 0., 0.),
+            width: 1,
+            depth_threshold: 0.05,
+            crease_angle: 60f32.to_radians(),
+        }
+    }
+}
+
+impl Outline {
+    //  Edge mask over the world-space position and normal of the nearest
+    //  surface at each pixel, rows from the top.
+    pub fn edges(
+        &self,
+        camera: &Camera,
+        width: usize,
+        height: usize,
+        geom: &[Option<(Vector3, Vector3)>],
+    ) -> Vec<bool> {
+        let view = camera.camera_transform();
+        let depth = geom
+            .iter()
+            .map(|g| {
+                g.map_or(f32::INFINITY, |(p, _)| {
+                    -(view * p.homo_point()).vec3_homo().v[2]
+                })
+            })
+            .collect::<Vec<f32>>();
+        let cos_crease = self.crease_angle.cos();
+        let r = self.width.max(1) as isize;
+
+        let mut ret = vec![false; width * height];
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let idx = (y * width as isize + x) as usize;
+                let Some((_, n)) = geom[idx] else {
+                    continue;
+                };
+                let d = depth[idx];
+                'search: for dy in -r..=r {
+                    for dx in -r..=r {
+                        let (nx, ny) = (x + dx, y + dy);
+                        if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                            continue;
+                        }
+                        let j = (ny * width as isize + nx) as usize;
+                        let edge = match geom[j] {
+                            None => true,
+                            Some((_, nj)) => {
+                                depth[j] - d > self.depth_threshold * d
+                                    || (depth[j] >= d && n.dot(nj) < cos_crease)
+                            }
+                        };
+                        if edge {
+                            ret[idx] = true;
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
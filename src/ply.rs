@@ -0,0 +1,270 @@
+use crate::{
+    linalg::{Vector2, Vector3},
+    model::Model,
+};
+use std::{error::Error, f32::consts::PI, fs, path::Path};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(match s {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(format!("ply: unknown type '{}'", s).into()),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Scalar::F32 | Scalar::F64)
+    }
+}
+
+struct Property {
+    name: String,
+    ty: Scalar,
+    //  Type of the length prefix for list properties.
+    list: Option<Scalar>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    props: Vec<Property>,
+}
+
+struct Body<'a> {
+    format: Format,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Body<'_> {
+    fn read(&mut self, ty: Scalar) -> Result<f64, Box<dyn Error>> {
+        if self.format == Format::Ascii {
+            while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            let start = self.pos;
+            while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            if start == self.pos {
+                return Err("ply: unexpected end of data".into());
+            }
+            return Ok(std::str::from_utf8(&self.data[start..self.pos])?.parse()?);
+        }
+        let n = ty.size();
+        let b = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or("ply: unexpected end of data")?;
+        self.pos += n;
+        let mut buf = [0u8; 8];
+        buf[..n].copy_from_slice(b);
+        if self.format == Format::BigEndian {
+            buf[..n].reverse();
+        }
+        Ok(match ty {
+            Scalar::I8 => buf[0] as i8 as f64,
+            Scalar::U8 => buf[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(buf),
+        })
+    }
+}
+
+//  Reads ASCII and binary PLY: positions, and where present normals,
+//  texture coordinates and colors of the `vertex` element, and polygons of
+//  the `face` element, fan-triangulated. Without normals, smooth ones are
+//  generated.
+pub fn load<P>(path: P) -> Result<Model, Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let bytes = fs::read(path)?;
+    let end = bytes
+        .windows(10)
+        .position(|w| w == b"end_header")
+        .ok_or("ply: no end_header")?;
+    let header = std::str::from_utf8(&bytes[..end])?;
+    let mut body_start = end + 10;
+    //  The header ends with a single line break, possibly CRLF.
+    if bytes.get(body_start) == Some(&b'\r') {
+        body_start += 1;
+    }
+    if bytes.get(body_start) == Some(&b'\n') {
+        body_start += 1;
+    }
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err("ply: missing magic".into());
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in lines {
+        let t = line.split_whitespace().collect::<Vec<&str>>();
+        match t.as_slice() {
+            ["format", f, ..] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(format!("ply: unknown format '{}'", f).into()),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse()?,
+                props: vec![],
+            }),
+            ["property", "list", len, ty, name] => elements
+                .last_mut()
+                .ok_or("ply: property outside of an element")?
+                .props
+                .push(Property {
+                    name: name.to_string(),
+                    ty: Scalar::parse(ty)?,
+                    list: Some(Scalar::parse(len)?),
+                }),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or("ply: property outside of an element")?
+                .props
+                .push(Property {
+                    name: name.to_string(),
+                    ty: Scalar::parse(ty)?,
+                    list: None,
+                }),
+            _ => {}
+        }
+    }
+    let mut body = Body {
+        format: format.ok_or("ply: no format line")?,
+        data: &bytes[body_start..],
+        pos: 0,
+    };
+
+    let mut ret = Model::new();
+    let mut has_normals = true;
+    for e in &elements {
+        let find = |names: &[&str]| {
+            e.props
+                .iter()
+                .position(|p| names.contains(&p.name.as_str()))
+        };
+        let (px, py, pz) = (find(&["x"]), find(&["y"]), find(&["z"]));
+        let (nx, ny, nz) = (find(&["nx"]), find(&["ny"]), find(&["nz"]));
+        let (tu, tv) = (
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        );
+        let (cr, cg, cb) = (
+            find(&["red", "r", "diffuse_red"]),
+            find(&["green", "g", "diffuse_green"]),
+            find(&["blue", "b", "diffuse_blue"]),
+        );
+        let fi = find(&["vertex_indices", "vertex_index"]);
+        if e.name == "vertex" {
+            has_normals = nx.is_some() && ny.is_some() && nz.is_some();
+        }
+        for _ in 0..e.count {
+            let mut vals = Vec::with_capacity(e.props.len());
+            let mut list = vec![];
+            for (k, p) in e.props.iter().enumerate() {
+                match p.list {
+                    Some(len) => {
+                        let n = body.read(len)? as usize;
+                        let items = (0..n)
+                            .map(|_| body.read(p.ty))
+                            .collect::<Result<Vec<f64>, _>>()?;
+                        if Some(k) == fi {
+                            list = items;
+                        }
+                        vals.push(0.);
+                    }
+                    None => vals.push(body.read(p.ty)?),
+                }
+            }
+            let get = |i: Option<usize>| i.map(|i| vals[i] as f32);
+            match e.name.as_str() {
+                "vertex" => {
+                    let pos = Vector3::new(
+                        get(px).ok_or("ply: vertex without x")?,
+                        get(py).ok_or("ply: vertex without y")?,
+                        get(pz).ok_or("ply: vertex without z")?,
+                    );
+                    let uv = get(tu).zip(get(tv)).map(|(u, v)| Vector2::new(u, v));
+                    let norm = has_normals.then(|| {
+                        Vector3::new(get(nx).unwrap(), get(ny).unwrap(), get(nz).unwrap())
+                    });
+                    let i = ret.add_vertex(pos, uv, norm);
+                    if let (Some(r), Some(g), Some(b)) = (cr, cg, cb) {
+                        //  Integer channels are 0-255.
+                        let scale = if e.props[r].ty.is_float() {
+                            1.
+                        } else {
+                            1. / 255.
+                        };
+                        ret.set_color(
+                            i,
+                            Vector3::new(vals[r] as f32, vals[g] as f32, vals[b] as f32) * scale,
+                        );
+                    }
+                }
+                "face" => {
+                    let n = ret.vertex_count();
+                    let idx = list.iter().map(|&x| x as usize).collect::<Vec<usize>>();
+                    if idx.iter().any(|&i| i >= n) {
+                        return Err("ply: face index out of range".into());
+                    }
+                    for w in idx.windows(2).skip(1) {
+                        ret.add_tri(idx[0], w[0], w[1]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    if !has_normals {
+        ret.compute_normals(PI);
+    }
+    Ok(ret)
+}
@@ -1,16 +1,62 @@
 use crate::{
+    background::Background,
     camera::Camera,
-    light::{Light, BP_P},
-    linalg::{Matrix4, Vector2, Vector3},
+    environment::Environment,
+    export,
+    fog::{Fog, FogColor},
+    light::Light,
+    linalg::{Matrix4, Vector2, Vector3, Vector4},
+    material::{Material, Shading},
     model::Model,
-    utils::{barycentric_2d, EPS},
+    node::{Node, NodeId},
+    outline::Outline,
+    shading::{shade_split, Lighting, Surface},
+    ssao::Ssao,
+    triangle::Triangle,
+    utils::{barycentric_2d, linear_to_srgb, EPS},
 };
-use std::f32::consts::PI;
+use std::{error::Error, f32::consts::PI, path::Path, rc::Rc};
+
+//  Result of `Scene::raycast`. `bary` weights the corners of triangle
+//  `triangle` of `model`, as instanced by `node`.
+#[derive(Clone)]
+pub struct RayHit {
+    pub node: NodeId,
+    pub model: Rc<Model>,
+    pub triangle: usize,
+    pub bary: Vector3,
+    pub distance: f32,
+}
 
 pub struct Scene {
     camera: Camera,
-    models: Vec<Model>,
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
     lights: Vec<Light>,
+    ambient: Vector3,
+    environment: Option<Rc<Environment>>,
+    background: Background,
+    ssao: Option<Ssao>,
+    outline: Option<Outline>,
+    fog: Option<Fog>,
+    mode: RenderMode,
+}
+
+//  Forward shading evaluates lighting for every sample that passes the
+//  depth test. Deferred shading first fills a G-buffer and then lights each
+//  visible sample once, which avoids paying for overdraw; for opaque
+//  geometry both produce the same image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    #[default]
+    Forward,
+    Deferred,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Scene {
@@ -23,24 +69,431 @@ impl Scene {
                 90. * PI / 180.,
                 1.,
             ),
-            models: vec![],
+            nodes: vec![],
+            roots: vec![],
             lights: vec![],
+            ambient: Vector3::new(0.03, 0.03, 0.03),
+            environment: None,
+            background: Background::default(),
+            ssao: None,
+            outline: None,
+            fog: None,
+            mode: RenderMode::default(),
         }
     }
     pub fn set_camera(&mut self, camera: Camera) {
         self.camera = camera;
     }
-    pub fn add_model(&mut self, model: Model) {
-        self.models.push(model);
+    pub fn add_model(&mut self, model: Model) -> NodeId {
+        self.add_node(Node::with_mesh(Rc::new(model)), None)
+    }
+    pub fn add_node(&mut self, mut node: Node, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        node.parent = parent;
+        node.children.clear();
+        match parent {
+            Some(p) => self.nodes[p.0].children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes.push(node);
+        id
+    }
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+    pub fn world_transform(&self, id: NodeId) -> Matrix4 {
+        let node = &self.nodes[id.0];
+        match node.parent {
+            Some(p) => self.world_transform(p) * node.transform.mat(),
+            None => node.transform.mat(),
+        }
+    }
+
+    //  Writes every mesh instance with its world transform baked in, see
+    //  `export::save_obj`.
+    pub fn save_obj<P>(&self, path: P) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        export::save_obj(path, &self.instances())
+    }
+
+    pub fn save_ply<P>(&self, path: P) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        export::save_ply(path, &self.instances())
+    }
+
+    //  Closest mesh triangle hit by the ray, from either side. Distances are
+    //  in world units. A zero or non-finite direction hits nothing.
+    pub fn raycast(&self, origin: Vector3, dir: Vector3) -> Option<RayHit> {
+        let len = dir.norm();
+        if !len.is_finite() || len <= EPS {
+            return None;
+        }
+        let dir = dir / len;
+        let mut ret: Option<RayHit> = None;
+        for (node, mesh, mat, _) in self.mesh_nodes() {
+            let Some(inv) = mat.inverse_affine() else {
+                continue;
+            };
+            //  With the direction left unnormalized in model space, the ray
+            //  parameter stays the world distance.
+            let (o, d) = (
+                (inv * origin.homo_point()).vec3_homo(),
+                (inv * dir.homo_vec()).vec3_homo(),
+            );
+            let t_max = ret.as_ref().map_or(f32::INFINITY, |h| h.distance);
+            if let Some(hit) = mesh.raycast(o, d, t_max) {
+                ret = Some(RayHit {
+                    node,
+                    model: mesh.clone(),
+                    triangle: hit.triangle,
+                    bary: hit.bary,
+                    distance: hit.t,
+                });
+            }
+        }
+        ret
+    }
+
+    //  Flattens the graph into (mesh, world matrix, material) triples,
+    //  materials inherited from the closest ancestor that overrides one.
+    fn instances(&self) -> Vec<(&Model, Matrix4, &Material)> {
+        self.mesh_nodes()
+            .into_iter()
+            .map(|(_, mesh, mat, mtl)| (mesh.as_ref(), mat, mtl))
+            .collect()
+    }
+
+    fn mesh_nodes(&self) -> Vec<(NodeId, &Rc<Model>, Matrix4, &Material)> {
+        let mut ret = vec![];
+        let mut stack = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&id| (id, Matrix4::identity(), None))
+            .collect::<Vec<(NodeId, Matrix4, Option<&Material>)>>();
+        while let Some((id, parent_mat, parent_mtl)) = stack.pop() {
+            let node = &self.nodes[id.0];
+            let mat = parent_mat * node.transform.mat();
+            let mtl = node.material.as_ref().or(parent_mtl);
+            if let Some(mesh) = &node.mesh {
+                ret.push((id, mesh, mat, mtl.unwrap_or(mesh.material())));
+            }
+            for &c in node.children.iter().rev() {
+                stack.push((c, mat, mtl));
+            }
+        }
+        ret
+    }
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
     }
-    pub fn add_light(&mut self, light: Light) {
+    pub fn add_light(&mut self, light: Light) -> usize {
         self.lights.push(light);
+        self.lights.len() - 1
+    }
+    pub fn light_mut(&mut self, id: usize) -> &mut Light {
+        &mut self.lights[id]
+    }
+    pub fn set_ambient(&mut self, ambient: Vector3) {
+        self.ambient = ambient;
+    }
+    //  Image-based lighting, added on top of the ambient color.
+    pub fn set_environment(&mut self, env: Option<Rc<Environment>>) {
+        self.environment = env;
+    }
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+    pub fn set_ssao(&mut self, ssao: Option<Ssao>) {
+        self.ssao = ssao;
+    }
+    pub fn set_outline(&mut self, outline: Option<Outline>) {
+        self.outline = outline;
+    }
+    pub fn set_fog(&mut self, fog: Option<Fog>) {
+        self.fog = fog;
+    }
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.mode = mode;
+    }
+    fn background_at(&self, i: usize, j: usize, width: usize, height: usize) -> Vector3 {
+        let (x, y) = (
+            2. * (i as f32 + 0.5) / width as f32 - 1.,
+            1. - 2. * (j as f32 + 0.5) / height as f32,
+        );
+        match &self.background {
+            Background::Color(c) => *c,
+            Background::Gradient { top, bottom } => *bottom + (*top - *bottom) * ((y + 1.) / 2.),
+            Background::Cubemap(cube) => cube.at_dir(self.camera.ray_dir(x, y)),
+            Background::Panorama(map) => map.at_dir(self.camera.ray_dir(x, y)),
+            Background::Environment => self
+                .environment
+                .as_ref()
+                .map_or(Vector3::new(0., 0., 0.), |env| {
+                    env.radiance(self.camera.ray_dir(x, y))
+                }),
+            Background::Transparent => Vector3::new(0., 0., 0.),
+        }
     }
     pub fn rasterize(&self, width: usize, height: usize, msaa: usize) -> Vec<u8> {
+        let (fb, _, msaa) = self.render_samples(width, height, msaa);
+        let ns = msaa * msaa;
+        //  Samples are clamped to the displayable range before averaging so
+        //  that bright HDR samples do not bleed over edges.
+        let mut fb_ret = vec![0u8; width * height * 3];
+        for (px, smp) in fb_ret.chunks_exact_mut(3).zip(fb.chunks_exact(ns * 3)) {
+            for (m, c) in px.iter_mut().enumerate() {
+                let sum = smp
+                    .iter()
+                    .skip(m)
+                    .step_by(3)
+                    .map(|x| x.clamp(0., 1.))
+                    .sum::<f32>();
+                *c = (sum / ns as f32 * 255.) as u8;
+            }
+        }
+        fb_ret
+    }
+    //  RGBA output. With a transparent background alpha is the fraction of
+    //  covered samples and color is averaged over covered samples only, so
+    //  edges composite without a dark fringe; otherwise alpha is opaque.
+    pub fn rasterize_rgba(&self, width: usize, height: usize, msaa: usize) -> Vec<u8> {
+        let (fb, zb, msaa) = self.render_samples(width, height, msaa);
+        let ns = msaa * msaa;
+        let transparent = matches!(self.background, Background::Transparent);
+        let mut fb_ret = vec![0u8; width * height * 4];
+        for (px, (smp, z)) in fb_ret
+            .chunks_exact_mut(4)
+            .zip(fb.chunks_exact(ns * 3).zip(zb.chunks_exact(ns)))
+        {
+            let cov = if transparent {
+                z.iter().filter(|z| z.is_finite()).count()
+            } else {
+                ns
+            };
+            if cov == 0 {
+                continue;
+            }
+            for m in 0..3 {
+                let sum = smp
+                    .chunks_exact(3)
+                    .zip(z)
+                    .filter(|(_, z)| !transparent || z.is_finite())
+                    .map(|(c, _)| c[m].clamp(0., 1.))
+                    .sum::<f32>();
+                px[m] = (sum / cov as f32 * 255.) as u8;
+            }
+            px[3] = (cov as f32 / ns as f32 * 255.).round() as u8;
+        }
+        fb_ret
+    }
+    //  Renders every sample, returning the display color and depth buffers,
+    //  `msaa * msaa` consecutive samples per pixel in rows from the top.
+    fn render_samples(
+        &self,
+        width: usize,
+        height: usize,
+        msaa: usize,
+    ) -> (Vec<f32>, Vec<f32>, usize) {
         assert!(msaa <= 16);
         assert!((width as f32 / height as f32 - self.camera.aspect).abs() < EPS);
         let msaa = if msaa <= 1 { 1 } else { msaa };
-        let mut fb = vec![0u8; width * height * 3 * msaa * msaa];
+        let ns = msaa * msaa;
+        let mut fb = vec![0f32; width * height * 3 * ns];
+        //  Per-pixel background, kept when the fog takes its color from it.
+        let mut bg = vec![];
+        let keep_bg = matches!(
+            self.fog,
+            Some(Fog {
+                color: FogColor::Background,
+                ..
+            })
+        );
+        for j in 0..height {
+            for i in 0..width {
+                let c = self.background_at(i, j, width, height);
+                if keep_bg {
+                    bg.push(c);
+                }
+                for k in 0..ns {
+                    let idx = (j * width + i) * ns + k;
+                    fb[idx * 3..idx * 3 + 3].copy_from_slice(&c.v);
+                }
+            }
+        }
+        let ctx = Lighting {
+            eye: self.camera.pos,
+            lights: &self.lights,
+            ambient: self.ambient,
+            env: self.environment.as_deref(),
+        };
+        let instances = self.instances();
+        //  With SSAO the ambient term is kept apart until the occlusion of
+        //  the nearest surface in each pixel is known. SSAO and outlines
+        //  both work on that nearest surface.
+        let mut amb = vec![];
+        let mut pix_geom = vec![];
+        let mut pix_z = vec![];
+        let screen_space = self.ssao.is_some() || self.outline.is_some();
+        if self.ssao.is_some() {
+            amb = vec![Vector3::new(0., 0., 0.); width * height * ns];
+        }
+        if screen_space {
+            pix_geom = vec![None; width * height];
+            pix_z = vec![f32::INFINITY; width * height];
+        }
+        //  Samples shaded in linear space, encoded once lighting is complete.
+        let mut linear = vec![false; width * height * ns];
+        let mut write =
+            |idx: usize, pos: Vector3, lit: (Vector3, Vector3), lin: bool, fb: &mut [f32]| {
+                let (direct, ambient) = match &self.fog {
+                    Some(fog) => {
+                        let f = fog.visibility(ctx.eye, pos);
+                        let color = match fog.color {
+                            FogColor::Color(c) => c,
+                            FogColor::Environment => {
+                                ctx.env.map_or(Vector3::new(0., 0., 0.), |env| {
+                                    env.prefiltered((pos - ctx.eye).normalize_or_zero(), 1.)
+                                })
+                            }
+                            FogColor::Background => bg[idx / ns],
+                        };
+                        (lit.0 * f + color * (1. - f), lit.1 * f)
+                    }
+                    None => lit,
+                };
+                let liv = if self.ssao.is_some() {
+                    amb[idx] = ambient;
+                    direct
+                } else {
+                    direct + ambient
+                };
+                fb[idx * 3..idx * 3 + 3].copy_from_slice(&liv.v);
+                linear[idx] = lin;
+            };
+        let mut track = |idx: usize, z: f32, pos: Vector3, norm: Vector3| {
+            let pix = idx / ns;
+            if screen_space && z < pix_z[pix] {
+                pix_z[pix] = z;
+                pix_geom[pix] = Some((pos, norm));
+            }
+        };
+
+        let zb = match self.mode {
+            RenderMode::Forward => {
+                self.rasterize_geometry(&instances, &ctx, width, height, msaa, |frag| {
+                    let lit = match frag.lit {
+                        Some((direct, ambient)) => {
+                            let tex = frag.material.texel(frag.uv);
+                            let e = frag.material.emissive_at(frag.uv);
+                            (direct * tex + e, ambient * tex)
+                        }
+                        None => {
+                            let albedo = frag.material.albedo_at(frag.uv) * frag.color;
+                            let s = Surface::with_albedo(
+                                frag.material,
+                                frag.pos,
+                                frag.norm,
+                                frag.uv,
+                                albedo,
+                            );
+                            shade_split(&s, &ctx)
+                        }
+                    };
+                    write(frag.idx, frag.pos, lit, frag.material.is_linear(), &mut fb);
+                    track(frag.idx, frag.z, frag.pos, frag.norm);
+                })
+            }
+            RenderMode::Deferred => {
+                let mut gbuf = vec![GSample::EMPTY; width * height * ns];
+                let zb = self.rasterize_geometry(&instances, &ctx, width, height, msaa, |frag| {
+                    gbuf[frag.idx] = GSample {
+                        pos: frag.pos,
+                        norm: frag.norm,
+                        albedo: match frag.lit {
+                            Some(_) => frag.material.texel(frag.uv),
+                            None => frag.material.albedo_at(frag.uv) * frag.color,
+                        },
+                        uv: frag.uv,
+                        lit: frag.lit,
+                        instance: frag.instance as u32,
+                    };
+                    track(frag.idx, frag.z, frag.pos, frag.norm);
+                });
+                for (idx, g) in gbuf.iter().enumerate() {
+                    if g.instance == u32::MAX {
+                        continue;
+                    }
+                    let material = instances[g.instance as usize].2;
+                    let lit = match g.lit {
+                        Some((direct, ambient)) => (
+                            direct * g.albedo + material.emissive_at(g.uv),
+                            ambient * g.albedo,
+                        ),
+                        None => {
+                            let s = Surface::with_albedo(material, g.pos, g.norm, g.uv, g.albedo);
+                            shade_split(&s, &ctx)
+                        }
+                    };
+                    write(idx, g.pos, lit, material.is_linear(), &mut fb);
+                }
+                zb
+            }
+        };
+
+        if let Some(ssao) = &self.ssao {
+            let ao = ssao.occlusion(&self.camera, width, height, &pix_geom);
+            for (idx, a) in amb.iter().enumerate() {
+                let a = *a * ao[idx / ns];
+                for m in 0..3 {
+                    fb[idx * 3 + m] += a.v[m];
+                }
+            }
+        }
+
+        for (c, _) in fb.chunks_exact_mut(3).zip(&linear).filter(|(_, l)| **l) {
+            for x in c {
+                *x = linear_to_srgb(x.max(0.));
+            }
+        }
+
+        if let Some(outline) = &self.outline {
+            let edges = outline.edges(&self.camera, width, height, &pix_geom);
+            for (idx, z) in zb.iter().enumerate() {
+                if edges[idx / ns] && z.is_finite() {
+                    fb[idx * 3..idx * 3 + 3].copy_from_slice(&outline.color.v);
+                }
+            }
+        }
+
+        (fb, zb, msaa)
+    }
+
+    //  Scan converts every instance and calls `frag` for each sample that
+    //  passes the depth test, in submission order, so a sample may be
+    //  reported again by a closer fragment. Returns the depth buffer.
+    fn rasterize_geometry<F>(
+        &self,
+        instances: &[(&Model, Matrix4, &Material)],
+        ctx: &Lighting,
+        width: usize,
+        height: usize,
+        msaa: usize,
+        mut frag: F,
+    ) -> Vec<f32>
+    where
+        F: FnMut(Fragment),
+    {
         let mut zb = vec![f32::INFINITY; width * height * msaa * msaa];
         let viewport_mat = Matrix4 {
             v: [
@@ -53,13 +506,41 @@ impl Scene {
 
         let cmat =
             viewport_mat * self.camera.perspective_transform() * self.camera.camera_transform();
-        for model in &self.models {
-            for tr in model.iter() {
-                let (p0, p1, p2) = (
-                    cmat * tr.v[0].homo_point(),
-                    cmat * tr.v[1].homo_point(),
-                    cmat * tr.v[2].homo_point(),
-                );
+        let frustum = self.camera.frustum();
+        for (instance, &(model, wmat, material)) in instances.iter().enumerate() {
+            //  Skip models entirely outside the view, the sphere test is
+            //  cheaper and the box test tighter.
+            if !frustum.intersects_sphere(&model.bounding_sphere().transform(wmat))
+                || !frustum.intersects_aabb(&model.aabb().transform(wmat))
+            {
+                continue;
+            }
+            let post = vertex_stage(model, wmat, cmat, material, ctx);
+            //  Primitive assembly.
+            for idx in model.indices() {
+                let pv = idx.map(|i| post[i as usize]);
+                let mut tr = Triangle {
+                    v: pv.map(|v| v.pos),
+                    n: pv.map(|v| v.norm),
+                    uv: pv.map(|v| v.uv),
+                    c: pv.map(|v| v.color),
+                };
+                let lit =
+                    (material.shading == Shading::Gouraud).then(|| pv.map(|v| v.lit.unwrap()));
+                let face = (tr.v[1] - tr.v[0])
+                    .cross(tr.v[2] - tr.v[0])
+                    .normalize_or_zero();
+                if material.shading == Shading::Flat {
+                    //  Agree with the vertex normals where the winding does
+                    //  not.
+                    let n = if face.dot(tr.n[0] + tr.n[1] + tr.n[2]) < 0. {
+                        -face
+                    } else {
+                        face
+                    };
+                    tr.n = [n; 3];
+                }
+                let [p0, p1, p2] = pv.map(|v| v.clip);
                 let (pc0, pc1, pc2) = (p0.vec3_homo(), p1.vec3_homo(), p2.vec3_homo());
                 let (l, r) = (
                     pc0.v[0].min(pc1.v[0]).min(pc2.v[0]).max(0.) as usize,
@@ -107,49 +588,27 @@ impl Scene {
                                 zb[buf_idx] = psz;
                                 c_smp += 1;
 
-                                let pos = tr.v[0] * a + tr.v[1] * b + tr.v[2] * c;
-                                let norm = (tr.n[0] * a + tr.n[1] * b + tr.n[2] * c).normalize();
-                                let uv = tr.uv[0] * a + tr.uv[1] * b + tr.uv[2] * c;
-                                let clr = if let Some(texture) = &tr.texture {
-                                    let uc = texture.at_uv(uv.v[0], uv.v[1]);
-                                    Vector3::new(uc[0] as f32, uc[1] as f32, uc[2] as f32)
-                                } else {
-                                    Vector3::new(255., 255., 255.)
-                                } / 255.;
-
-                                //  Blinn-Phong shading
-                                let mut liv = Vector3::new(0., 0., 0.);
-                                let ambient = Vector3::new(0.03, 0.03, 0.03);
-                                for light in &self.lights {
-                                    match light {
-                                        &Light::Point { pos: lp, li } => {
-                                            let dist = (lp - pos).norm();
-                                            let id2 = 1. / (dist * dist);
-                                            let l = (lp - pos).normalize();
-                                            let v = (self.camera.pos - pos).normalize();
-                                            let h = (l + v).normalize();
-                                            let diff = clr * (li * id2) * norm.dot(l).max(0.);
-                                            let spec = Vector3::new(1., 1., 1.)
-                                                * (li * id2)
-                                                * norm.dot(h).max(0.).powf(BP_P);
-                                            liv += ambient + diff + spec;
-                                        }
-                                        &Light::Parallel { dir: ld, li } => {
-                                            let l = ld.normalize();
-                                            let v = (self.camera.pos - pos).normalize();
-                                            let h = (l + v).normalize();
-                                            let diff = clr * li * norm.dot(l).max(0.);
-                                            let spec = Vector3::new(1., 1., 1.)
-                                                * li
-                                                * norm.dot(h).max(0.).powf(BP_P);
-                                            liv += ambient + diff + spec;
-                                        }
-                                    }
-                                }
-
-                                for m in 0..3 {
-                                    fb[buf_idx * 3 + m] = (liv.v[m].max(0.).min(1.) * 255.) as u8;
-                                }
+                                //  Models without usable normals are lit
+                                //  with the face normal.
+                                let n =
+                                    (tr.n[0] * a + tr.n[1] * b + tr.n[2] * c).normalize_or_zero();
+                                let n = if n.norm() > EPS { n } else { face };
+                                frag(Fragment {
+                                    idx: buf_idx,
+                                    z: psz,
+                                    instance,
+                                    material,
+                                    pos: tr.v[0] * a + tr.v[1] * b + tr.v[2] * c,
+                                    norm: n,
+                                    uv: tr.uv[0] * a + tr.uv[1] * b + tr.uv[2] * c,
+                                    color: tr.c[0] * a + tr.c[1] * b + tr.c[2] * c,
+                                    lit: lit.map(|l| {
+                                        (
+                                            l[0].0 * a + l[1].0 * b + l[2].0 * c,
+                                            l[0].1 * a + l[1].1 * b + l[2].1 * c,
+                                        )
+                                    }),
+                                });
                             }
                         }
 
@@ -162,29 +621,193 @@ impl Scene {
                 }
             }
         }
+        zb
+    }
+}
 
-        if msaa <= 1 {
-            fb
-        } else {
-            let mut fb_ret = vec![0u8; width * height * 3];
-            for i in 0..width {
-                for j in 0..height {
-                    let mut px = [0., 0., 0.];
-                    for k in 0..msaa {
-                        for l in 0..msaa {
-                            let idx = (j * width + i) * msaa * msaa + l * msaa + k;
-                            for m in 0..3 {
-                                px[m] += fb[idx * 3 + m] as f32;
-                            }
-                        }
-                    }
-                    for m in 0..3 {
-                        px[m] /= (msaa * msaa) as f32;
-                        fb_ret[(i + j * width) * 3 + m] = px[m] as u8;
-                    }
-                }
+//  A vertex after the world and camera transforms.
+#[derive(Clone, Copy)]
+struct PostVertex {
+    pos: Vector3,
+    norm: Vector3,
+    uv: Vector2,
+    color: Vector3,
+    clip: Vector4,
+    //  Vertex lighting (direct, ambient) for Gouraud shading.
+    lit: Option<(Vector3, Vector3)>,
+}
+
+//  Transforms every vertex of the model once, into world space and, with
+//  `cmat`, into clip space. Triangles are then assembled from the result by
+//  index.
+fn vertex_stage(
+    model: &Model,
+    wmat: Matrix4,
+    cmat: Matrix4,
+    material: &Material,
+    ctx: &Lighting,
+) -> Vec<PostVertex> {
+    let nmat = wmat.normal_matrix();
+    model
+        .vertices()
+        .iter()
+        .map(|v| {
+            let pos = (wmat * v.pos.homo_point()).vec3_homo();
+            let norm = (nmat * v.norm).normalize_or_zero();
+            //  Vertices are lit with the base and vertex colors and the base
+            //  color map modulates the result per fragment.
+            let lit = (material.shading == Shading::Gouraud).then(|| {
+                let mut s =
+                    Surface::with_albedo(material, pos, norm, v.uv, material.base_color * v.color);
+                //  Emission is added per fragment.
+                s.emissive = Vector3::new(0., 0., 0.);
+                shade_split(&s, ctx)
+            });
+            PostVertex {
+                pos,
+                norm,
+                uv: v.uv,
+                color: v.color,
+                clip: cmat * pos.homo_point(),
+                lit,
             }
-            fb_ret
+        })
+        .collect()
+}
+
+//  A sample that passed the depth test, attributes interpolated.
+struct Fragment<'a> {
+    idx: usize,
+    z: f32,
+    instance: usize,
+    material: &'a Material,
+    pos: Vector3,
+    norm: Vector3,
+    uv: Vector2,
+    color: Vector3,
+    //  Interpolated vertex lighting (direct, ambient) for Gouraud shading.
+    lit: Option<(Vector3, Vector3)>,
+}
+
+//  G-buffer entry of the deferred path, `instance` is `u32::MAX` where no
+//  geometry was drawn.
+#[derive(Clone, Copy)]
+struct GSample {
+    pos: Vector3,
+    norm: Vector3,
+    albedo: Vector3,
+    uv: Vector2,
+    lit: Option<(Vector3, Vector3)>,
+    instance: u32,
+}
+
+impl GSample {
+    const EMPTY: Self = Self {
+        pos: Vector3 { v: [0.; 3] },
+        norm: Vector3 { v: [0.; 3] },
+        albedo: Vector3 { v: [0.; 3] },
+        uv: Vector2 { v: [0.; 2] },
+        lit: None,
+        instance: u32::MAX,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linalg::transform::Transform;
+
+    fn octahedron() -> Model {
+        let mut m = Model::new();
+        let [px, nx, py, ny, pz, nz] = [
+            (1., 0., 0.),
+            (-1., 0., 0.),
+            (0., 1., 0.),
+            (0., -1., 0.),
+            (0., 0., 1.),
+            (0., 0., -1.),
+        ]
+        .map(|(x, y, z)| m.add_vertex(Vector3::new(x, y, z), None, None));
+        for (a, b) in [(px, py), (py, nx), (nx, ny), (ny, px)] {
+            m.add_tri(a, b, pz);
+            m.add_tri(b, a, nz);
         }
+        m.compute_normals(PI);
+        m
+    }
+
+    #[test]
+    fn rasterize_without_normals() {
+        let mut m = Model::new();
+        let [a, b, c] = [(-1., -1.), (1., -1.), (0., 1.)]
+            .map(|(x, y)| m.add_vertex(Vector3::new(x, y, -2.), None, None));
+        m.add_tri(a, b, c);
+        let mut scene = Scene::new();
+        scene.add_model(m);
+        scene.add_light(Light::Parallel {
+            dir: Vector3::new(0., 0., 1.),
+            li: Vector3::new(1., 1., 1.),
+        });
+        let buf = scene.rasterize(16, 16, 1);
+        //  Lit from the front by the face normal.
+        let center = (8 * 16 + 8) * 3;
+        assert!(buf[center] > 200);
+    }
+
+    #[test]
+    fn deferred_matches_forward() {
+        let mesh = Rc::new(octahedron());
+        let mut scene = Scene::new();
+        scene.set_camera(Camera::look_at(
+            Vector3::new(0.5, 1., 5.),
+            Vector3::new(0., 0., 0.),
+            Vector3::new(0., 1., 0.),
+            0.8,
+            1.,
+        ));
+        let materials = [
+            Material::new(),
+            Material::new().shading(Shading::Gouraud),
+            Material::pbr(Vector3::new(0.8, 0.3, 0.2), 0.5, 0.4),
+            Material::toon(Vector3::new(0.2, 0.6, 0.9), 3),
+        ];
+        for (i, mtl) in materials.into_iter().enumerate() {
+            let x = i as f32 * 1.2 - 1.8;
+            let t = Transform::new()
+                .scale(Vector3::new(0.5, 0.5, 0.5))
+                .translation(Vector3::new(x, 0., -(i as f32) * 0.3));
+            scene.add_node(
+                Node::with_mesh(mesh.clone()).transform(t).material(mtl),
+                None,
+            );
+        }
+        scene.add_light(Light::Point {
+            pos: Vector3::new(2., 3., 3.),
+            li: Vector3::new(8., 8., 8.),
+            att: Default::default(),
+        });
+        scene.set_ssao(Some(Ssao::default()));
+        let forward = scene.rasterize(64, 64, 2);
+        scene.set_render_mode(RenderMode::Deferred);
+        let deferred = scene.rasterize(64, 64, 2);
+        assert!(forward.iter().filter(|&&c| c > 0).count() > 1000);
+        assert_eq!(forward, deferred);
+    }
+
+    #[test]
+    fn raycast_degenerate_direction() {
+        let mut m = Model::new();
+        let [a, b, c] = [(-1., -1.), (1., -1.), (0., 1.)]
+            .map(|(x, y)| m.add_vertex(Vector3::new(x, y, -2.), None, None));
+        m.add_tri(a, b, c);
+        let mut scene = Scene::new();
+        scene.add_model(m);
+        let origin = Vector3::new(0., 0., 0.);
+        let hit = scene.raycast(origin, Vector3::new(0., 0., -3.)).unwrap();
+        assert!((hit.distance - 2.).abs() < 1e-5);
+        assert!(scene.raycast(origin, Vector3::new(0., 0., 0.)).is_none());
+        assert!(scene
+            .raycast(origin, Vector3::new(f32::NAN, 0., -1.))
+            .is_none());
     }
 }
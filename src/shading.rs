@@ -0,0 +1,185 @@
+use crate::{
+    environment::Environment,
+    light::Light,
+    linalg::{Vector2, Vector3},
+    material::{Material, ShadingModel},
+    texture::Texture,
+};
+use std::{f32::consts::PI, rc::Rc};
+
+//  Material inputs resolved at one surface point, textures already sampled.
+#[derive(Clone, Debug)]
+pub struct Surface {
+    pub model: ShadingModel,
+    pub pos: Vector3,
+    pub norm: Vector3,
+    pub albedo: Vector3,
+    pub specular: Vector3,
+    pub shininess: f32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub ao: f32,
+    pub emissive: Vector3,
+    pub bands: usize,
+    pub ramp: Option<Rc<Texture>>,
+}
+
+impl Surface {
+    pub fn new(material: &Material, pos: Vector3, norm: Vector3, uv: Vector2) -> Self {
+        Self::with_albedo(material, pos, norm, uv, material.albedo_at(uv))
+    }
+
+    //  As `new`, with the base color already resolved.
+    pub fn with_albedo(
+        material: &Material,
+        pos: Vector3,
+        norm: Vector3,
+        uv: Vector2,
+        albedo: Vector3,
+    ) -> Self {
+        let (u, v) = (uv.v[0], uv.v[1]);
+        let mut ret = Self {
+            model: material.model,
+            pos,
+            norm,
+            albedo,
+            specular: material.specular,
+            shininess: material.shininess,
+            metallic: material.metallic,
+            roughness: material.roughness,
+            ao: material.ao,
+            emissive: material.emissive_at(uv),
+            bands: material.bands,
+            ramp: material.ramp.clone(),
+        };
+        if let Some(texture) = &material.metallic_roughness_map {
+            let mr = texture.sample(u, v);
+            ret.roughness *= mr.v[1];
+            ret.metallic *= mr.v[2];
+        }
+        if let Some(texture) = &material.occlusion_map {
+            ret.ao *= texture.sample(u, v).v[0];
+        }
+        ret
+    }
+}
+
+//  Everything in the scene a surface is lit by.
+pub struct Lighting<'a> {
+    pub eye: Vector3,
+    pub lights: &'a [Light],
+    pub ambient: Vector3,
+    pub env: Option<&'a Environment>,
+}
+
+pub fn shade(s: &Surface, ctx: &Lighting) -> Vector3 {
+    let (direct, ambient) = shade_split(s, ctx);
+    direct + ambient
+}
+
+//  Direct lighting (including emission) and ambient lighting separately, so
+//  that ambient occlusion can be applied to the latter only.
+pub fn shade_split(s: &Surface, ctx: &Lighting) -> (Vector3, Vector3) {
+    let (direct, ambient) = reflected(s, ctx);
+    (direct + s.emissive, ambient)
+}
+
+fn reflected(s: &Surface, ctx: &Lighting) -> (Vector3, Vector3) {
+    let v = (ctx.eye - s.pos).normalize();
+    match s.model {
+        ShadingModel::BlinnPhong => {
+            let mut amb = s.albedo * ctx.ambient;
+            if let Some(env) = ctx.env {
+                amb += s.albedo * env.irradiance(s.norm) / PI;
+            }
+            let mut liv = Vector3::new(0., 0., 0.);
+            for light in ctx.lights {
+                light.illuminate(s.pos, |l, li| {
+                    let h = (l + v).normalize();
+                    let diff = s.albedo * li * s.norm.dot(l).max(0.);
+                    let spec = s.specular * li * s.norm.dot(h).max(0.).powf(s.shininess);
+                    liv += diff + spec;
+                });
+            }
+            (liv, amb)
+        }
+        ShadingModel::Toon => {
+            let mut amb = s.albedo * ctx.ambient;
+            if let Some(env) = ctx.env {
+                amb += s.albedo * env.irradiance(s.norm) / PI;
+            }
+            let bands = s.bands.max(1) as f32;
+            let mut liv = Vector3::new(0., 0., 0.);
+            for light in ctx.lights {
+                light.illuminate(s.pos, |l, li| {
+                    let nl = s.norm.dot(l).max(0.);
+                    let diff = match &s.ramp {
+                        Some(ramp) => ramp.sample(nl, 0.5),
+                        None => Vector3::new(1., 1., 1.) * ((nl * bands).ceil() / bands),
+                    };
+                    let h = (l + v).normalize();
+                    let spec = if nl > 0. && s.norm.dot(h).max(0.).powf(s.shininess) > 0.5 {
+                        s.specular
+                    } else {
+                        Vector3::new(0., 0., 0.)
+                    };
+                    liv += (s.albedo * diff + spec) * li;
+                });
+            }
+            (liv, amb)
+        }
+        ShadingModel::Pbr => {
+            let mut amb = s.albedo * ctx.ambient * (s.ao * (1. - s.metallic));
+            if let Some(env) = ctx.env {
+                amb += ibl(s, v, env);
+            }
+            let mut liv = Vector3::new(0., 0., 0.);
+            for light in ctx.lights {
+                light.illuminate(s.pos, |l, li| {
+                    liv += cook_torrance(s, l, v) * li * s.norm.dot(l).max(0.);
+                });
+            }
+            (liv, amb)
+        }
+    }
+}
+
+//  Split-sum image-based lighting: SH irradiance for the diffuse lobe and
+//  prefiltered radiance scaled by the BRDF table for the specular lobe.
+fn ibl(s: &Surface, v: Vector3, env: &Environment) -> Vector3 {
+    let n = s.norm;
+    let nv = n.dot(v).max(1e-4);
+    let r = n * (2. * n.dot(v)) - v;
+    let f0 = Vector3::new(0.04, 0.04, 0.04) * (1. - s.metallic) + s.albedo * s.metallic;
+    let ab = env.brdf(nv, s.roughness);
+    let spec =
+        env.prefiltered(r, s.roughness) * (f0 * ab.v[0] + Vector3::new(1., 1., 1.) * ab.v[1]);
+    let kd = (Vector3::new(1., 1., 1.) - f0) * (1. - s.metallic);
+    let diff = kd * s.albedo * env.irradiance(n) / PI;
+    (diff + spec) * s.ao
+}
+
+//  GGX distribution, height-correlated Smith visibility and Schlick
+//  Fresnel, the BRDF of the glTF 2.0 reference implementation.
+fn cook_torrance(s: &Surface, l: Vector3, v: Vector3) -> Vector3 {
+    let n = s.norm;
+    let (nl, nv) = (n.dot(l), n.dot(v).max(1e-4));
+    if nl <= 0. {
+        return Vector3::new(0., 0., 0.);
+    }
+    let h = (l + v).normalize_or_zero();
+    let (nh, vh) = (n.dot(h).max(0.), v.dot(h).max(0.));
+    let a = (s.roughness * s.roughness).max(1e-3);
+    let a2 = a * a;
+
+    let dd = nh * nh * (a2 - 1.) + 1.;
+    let d = a2 / (PI * dd * dd);
+    let vis = 0.5
+        / (nl * (nv * nv * (1. - a2) + a2).sqrt() + nv * (nl * nl * (1. - a2) + a2).sqrt())
+            .max(1e-6);
+    let f0 = Vector3::new(0.04, 0.04, 0.04) * (1. - s.metallic) + s.albedo * s.metallic;
+    let f = f0 + (Vector3::new(1., 1., 1.) - f0) * (1. - vh).powi(5);
+
+    let diffuse = (Vector3::new(1., 1., 1.) - f) * s.albedo * ((1. - s.metallic) / PI);
+    diffuse + f * (d * vis)
+}
@@ -0,0 +1,171 @@
+use crate::{
+    camera::Camera,
+    linalg::Vector3,
+    utils::{hash_unit, EPS},
+};
+
+//  Screen-space ambient occlusion over a normal-oriented hemisphere.
+//  `radius` is in world units, `blur` is the half-width in pixels of the
+//  depth-aware box filter that removes the per-pixel rotation noise.
+#[derive(Clone, Copy, Debug)]
+pub struct Ssao {
+    pub radius: f32,
+    pub samples: usize,
+    pub blur: usize,
+    pub bias: f32,
+    pub strength: f32,
+}
+
+impl Default for Ssao {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            samples: 16,
+            blur: 2,
+            bias: 0.02,
+            strength: 1.,
+        }
+    }
+}
+
+impl Ssao {
+    //  Per-pixel ambient visibility in [0, 1] from the world-space position
+    //  and normal of the nearest surface at each pixel, rows from the top.
+    pub fn occlusion(
+        &self,
+        camera: &Camera,
+        width: usize,
+        height: usize,
+        geom: &[Option<(Vector3, Vector3)>],
+    ) -> Vec<f32> {
+        let view = camera.camera_transform();
+        let proj = camera.perspective_transform();
+        let to_view = |p: Vector3| (view * p.homo_point()).vec3_homo();
+        //  View-space depth (distance along the view direction) per pixel.
+        let depth = geom
+            .iter()
+            .map(|g| g.map_or(f32::INFINITY, |(p, _)| -to_view(p).v[2]))
+            .collect::<Vec<f32>>();
+
+        let mut ao = vec![1f32; width * height];
+        for (idx, g) in geom.iter().enumerate() {
+            let Some((p, n)) = *g else {
+                continue;
+            };
+            let pv = to_view(p);
+            let nv = (view * n.homo_vec()).vec3_homo().normalize_or_zero();
+            if nv.norm() < EPS {
+                continue;
+            }
+            //  Random rotation of the kernel about the normal per pixel.
+            let seed = (idx as u32).wrapping_mul(0x9e37);
+            let rnd = Vector3::new(
+                hash_unit(seed) * 2. - 1.,
+                hash_unit(seed.wrapping_add(1)) * 2. - 1.,
+                0.,
+            );
+            let t = (rnd - nv * rnd.dot(nv)).normalize_or_zero();
+            let t = if t.norm() < EPS { nv.basis().0 } else { t };
+            let b = nv.cross(t);
+
+            let mut occ = 0.;
+            for k in 0..self.samples {
+                let s = seed.wrapping_add((k as u32).wrapping_mul(3).wrapping_add(2));
+                let (u, v, w) = (
+                    hash_unit(s),
+                    hash_unit(s.wrapping_add(1)),
+                    hash_unit(s.wrapping_add(2)),
+                );
+                //  Cosine-ish hemisphere direction, denser near the center.
+                let (phi, ct) = (2. * std::f32::consts::PI * u, v.sqrt());
+                let st = (1. - ct * ct).sqrt();
+                let scale = 0.1 + 0.9 * ((k as f32 + w) / self.samples as f32).powi(2);
+                let dir = t * (st * phi.cos()) + b * (st * phi.sin()) + nv * ct;
+                let sp = pv + dir * (self.radius * scale);
+
+                let clip = proj * sp.homo_point();
+                if clip.v[3] <= EPS {
+                    continue;
+                }
+                let ndc = clip.vec3_homo();
+                let (x, y) = (
+                    ((ndc.v[0] + 1.) / 2. * width as f32) as isize,
+                    ((1. - ndc.v[1]) / 2. * height as f32) as isize,
+                );
+                if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+                    continue;
+                }
+                let scene_d = depth[y as usize * width + x as usize];
+                let sample_d = -sp.v[2];
+                if scene_d < sample_d - self.bias {
+                    //  Fade out occluders far outside the sampling radius.
+                    let range = (self.radius / (-pv.v[2] - scene_d).abs().max(EPS)).min(1.);
+                    occ += range;
+                }
+            }
+            ao[idx] = 1. - occ / self.samples.max(1) as f32;
+        }
+        self.blur(&ao, &depth, width, height)
+    }
+
+    fn blur(&self, ao: &[f32], depth: &[f32], width: usize, height: usize) -> Vec<f32> {
+        let r = self.blur as isize;
+        let mut ret = vec![1f32; width * height];
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let idx = (y * width as isize + x) as usize;
+                let d = depth[idx];
+                if !d.is_finite() {
+                    continue;
+                }
+                let (mut sum, mut cnt) = (0., 0.);
+                for dy in -r..=r {
+                    for dx in -r..=r {
+                        let (nx, ny) = (x + dx, y + dy);
+                        if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                            continue;
+                        }
+                        let j = (ny * width as isize + nx) as usize;
+                        //  Do not blur across depth discontinuities.
+                        if (depth[j] - d).abs() < self.radius {
+                            sum += ao[j];
+                            cnt += 1.;
+                        }
+                    }
+                }
+                let v = if cnt > 0. { sum / cnt } else { ao[idx] };
+                ret[idx] = 1. - (1. - v) * self.strength;
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //  Seeds past 326x326 pixels overflow u32 without wrapping arithmetic,
+    //  which debug builds catch.
+    #[test]
+    fn occlusion_on_large_frame() {
+        let (w, h) = (400, 400);
+        let cam = Camera::look_at(
+            Vector3::new(0., 0., 3.),
+            Vector3::new(0., 0., 0.),
+            Vector3::new(0., 1., 0.),
+            1.,
+            1.,
+        );
+        let n = Vector3::new(0., 0., 1.);
+        let geom = (0..w * h)
+            .map(|i| {
+                let (x, y) = ((i % w) as f32 / w as f32, (i / w) as f32 / h as f32);
+                Some((Vector3::new(x * 2. - 1., 1. - y * 2., 0.), n))
+            })
+            .collect::<Vec<_>>();
+        let ao = Ssao::default().occlusion(&cam, w, h, &geom);
+        assert_eq!(ao.len(), w * h);
+        assert!(ao.iter().all(|a| (0. ..=1.).contains(a)));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{linalg::Vector3, model::Model};
+use std::{error::Error, fs, path::Path};
+
+//  Faces meeting at less than this keep a shared, smoothed normal.
+const CREASE_ANGLE: f32 = 30. * std::f32::consts::PI / 180.;
+
+//  Reads ASCII and binary STL. Facets arrive as unconnected triangles, so
+//  coincident corners are welded and normals are regenerated with a crease
+//  angle rather than taken from the file.
+pub fn load<P>(path: P) -> Result<Model, Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let bytes = fs::read(path)?;
+    let tris = if is_binary(&bytes) {
+        binary(&bytes)?
+    } else {
+        ascii(std::str::from_utf8(&bytes)?)?
+    };
+
+    let mut ret = Model::new();
+    let (mut lo, mut hi) = (
+        Vector3::new(f32::MAX, f32::MAX, f32::MAX),
+        Vector3::new(f32::MIN, f32::MIN, f32::MIN),
+    );
+    for t in &tris {
+        let i = t.map(|p| {
+            for k in 0..3 {
+                lo.v[k] = lo.v[k].min(p.v[k]);
+                hi.v[k] = hi.v[k].max(p.v[k]);
+            }
+            ret.add_vertex(p, None, None)
+        });
+        ret.add_tri(i[0], i[1], i[2]);
+    }
+    if !tris.is_empty() {
+        ret.weld(((hi - lo).norm() * 1e-6).max(f32::MIN_POSITIVE));
+    }
+    ret.compute_normals(CREASE_ANGLE);
+    Ok(ret)
+}
+
+//  Binary files may also start with "solid", so go by the size implied by
+//  the facet count.
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < 84 {
+        return false;
+    }
+    let n = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    binary_len(n) == Some(bytes.len()) || !bytes.starts_with(b"solid")
+}
+
+//  Header, facet count and 50 bytes per facet.
+fn binary_len(n: usize) -> Option<usize> {
+    n.checked_mul(50)?.checked_add(84)
+}
+
+fn binary(bytes: &[u8]) -> Result<Vec<[Vector3; 3]>, Box<dyn Error>> {
+    let n = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    let f = |o: usize| f32::from_le_bytes([bytes[o], bytes[o + 1], bytes[o + 2], bytes[o + 3]]);
+    if binary_len(n).is_none_or(|len| len > bytes.len()) {
+        return Err("stl: truncated".into());
+    }
+    let mut ret = Vec::with_capacity(n);
+    for i in 0..n {
+        //  Skip the facet normal.
+        let o = 84 + 50 * i + 12;
+        ret.push([0, 1, 2].map(|k| {
+            let p = o + 12 * k;
+            Vector3::new(f(p), f(p + 4), f(p + 8))
+        }));
+    }
+    Ok(ret)
+}
+
+fn ascii(text: &str) -> Result<Vec<[Vector3; 3]>, Box<dyn Error>> {
+    let mut ret = vec![];
+    let mut corners = vec![];
+    for line in text.lines() {
+        let t = line.split_whitespace().collect::<Vec<&str>>();
+        match t.as_slice() {
+            ["vertex", x, y, z] => corners.push(Vector3::new(x.parse()?, y.parse()?, z.parse()?)),
+            ["endloop"] => {
+                if corners.len() != 3 {
+                    return Err("stl: facet is not a triangle".into());
+                }
+                ret.push([corners[0], corners[1], corners[2]]);
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facets(n: u32, stored: usize) -> Vec<u8> {
+        let mut ret = vec![0u8; 80];
+        ret.extend(n.to_le_bytes());
+        ret.resize(84 + 50 * stored, 0);
+        ret
+    }
+
+    #[test]
+    fn binary_reads_every_facet() {
+        assert_eq!(binary(&facets(2, 2)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn binary_rejects_truncated() {
+        assert!(binary(&facets(3, 2)).is_err());
+        assert!(binary(&facets(u32::MAX, 1)).is_err());
+    }
+}
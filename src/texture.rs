@@ -1,3 +1,4 @@
+use crate::{linalg::Vector3, utils::srgb_to_linear};
 use image::{ImageResult, RgbImage};
 
 #[derive(Debug)]
@@ -20,6 +21,13 @@ impl Texture {
         })
     }
 
+    pub fn save<P>(&self, path: P) -> ImageResult<()>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.img.save(path)
+    }
+
     pub fn at_uv(&self, u: f32, v: f32) -> [u8; 3] {
         let (w, h) = self.img.dimensions();
         let (x, y) = (u * (w - 1) as f32, (1. - v) * (h - 1) as f32);
@@ -47,4 +55,17 @@ impl Texture {
             (pxx1[2] * (1. - t) + pxx2[2] * t) as u8,
         ]
     }
+
+    //  Bilinear lookup normalized to [0, 1].
+    pub fn sample(&self, u: f32, v: f32) -> Vector3 {
+        let c = self.at_uv(u, v);
+        Vector3::new(c[0] as f32, c[1] as f32, c[2] as f32) / 255.
+    }
+
+    //  As `sample`, decoded from sRGB to linear.
+    pub fn sample_srgb(&self, u: f32, v: f32) -> Vector3 {
+        let mut ret = self.sample(u, v);
+        ret.v = ret.v.map(srgb_to_linear);
+        ret
+    }
 }
@@ -22,3 +22,30 @@ pub fn barycentric_2d(tri: [Vector2; 3], p: Vector2) -> (f32, f32, f32) {
     let (a, b) = (l1.cross(v1) / l1.cross(-l0), l2.cross(v2) / l2.cross(-l1));
     (a, b, 1. - a - b)
 }
+
+//  Integer hash (lowbias32) mapped to [0, 1).
+pub fn hash_unit(mut x: u32) -> f32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    (x >> 8) as f32 / (1u32 << 24) as f32
+}
+
+//  sRGB transfer function, per channel on [0, 1].
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}